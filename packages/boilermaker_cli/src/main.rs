@@ -43,6 +43,8 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
+    #[command(about = "Apply a template to an existing project")]
+    Apply(commands::Apply),
     #[command(subcommand, about = "Get and set system config")]
     Config(commands::Config),
     #[command(subcommand, about = "Documentation")]
@@ -67,6 +69,8 @@ enum Commands {
     Sources(commands::Sources),
     #[command(about = "Update an installed template")]
     Update(commands::Update),
+    #[command(about = "Upgrade a generated project to the installed template version")]
+    Upgrade(commands::Upgrade),
 }
 
 #[tokio::main]
@@ -76,7 +80,9 @@ async fn main() -> Result<()> {
 
     let cli = Cli::parse();
 
-    logging::init_tracing(cli.debug)?;
+    let stdout_is_output =
+        matches!(&cli.command, Some(Commands::New(cmd)) if cmd.writes_to_stdout());
+    logging::init_tracing(cli.debug, stdout_is_output)?;
 
     // TODO: decide where a remote db should be allowed vs just searching remote and installing
     // locally
@@ -96,9 +102,11 @@ async fn main() -> Result<()> {
 
     {
         let db = app_state.local_db.clone();
-        if !db.template_table_exists().await? {
-            db.create_schema().await?;
+        // Migrate before anything else queries the pool: a connection that has already read the
+        // old schema can return rows that don't match it.
+        db.create_schema().await?;
 
+        if db.get_docs().await?.is_empty() {
             let idx_docs_opts = Some(IndexDocsOptions { dev: is_dev_env });
             db.index_docs(idx_docs_opts).await?;
         }
@@ -112,6 +120,7 @@ async fn main() -> Result<()> {
 
     // TODO: clean this up with aliases or direct imports.
     match command {
+        Commands::Apply(cmd) => commands::apply(&app_state, &cmd).await,
         Commands::Config(subcmd) => match subcmd {
             Config::Get(cmd) => config::get(&app_state, &cmd).await,
         },
@@ -149,5 +158,6 @@ async fn main() -> Result<()> {
             },
         },
         Commands::Update(cmd) => commands::update(&app_state, &cmd).await,
+        Commands::Upgrade(cmd) => commands::upgrade(&app_state, &cmd).await,
     }
 }
@@ -0,0 +1,3 @@
+-- template commit down
+
+ALTER TABLE template DROP COLUMN commit_sha;
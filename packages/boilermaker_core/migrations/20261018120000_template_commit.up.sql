@@ -0,0 +1,3 @@
+-- template commit up
+
+ALTER TABLE template ADD COLUMN commit_sha TEXT;
@@ -0,0 +1,86 @@
+use std::{env, path::PathBuf};
+
+use clap::Parser;
+use color_eyre::{Result, eyre::eyre};
+use tracing::info;
+
+use crate::{
+    commands::new::{RenderArgs, post_new_hooks, print_plan, render_project, setup_template},
+    state::AppState,
+    template as tpl,
+    util::{file::remove_dir_if_exists, output::print_table},
+};
+
+#[derive(Debug, Parser)]
+pub struct Apply {
+    #[command(flatten)]
+    pub render: RenderArgs,
+    #[arg(
+        short,
+        long,
+        help = "Project dir to apply the template to (default: current dir)"
+    )]
+    pub dir: Option<PathBuf>,
+    #[arg(
+        short = 'c',
+        long,
+        value_enum,
+        default_value_t = tpl::ConflictPolicy::Prompt,
+        help = "What to do with existing files that differ from the template"
+    )]
+    pub conflict: tpl::ConflictPolicy,
+}
+
+// Renders the template into a temporary work_dir like `new`, then writes it into an existing
+// project_dir file by file instead of creating a new one.
+#[tracing::instrument]
+pub async fn apply(app_state: &AppState, cmd: &Apply) -> Result<()> {
+    let project_dir = match &cmd.dir {
+        Some(dir) => dir.clone(),
+        None => env::current_dir()?,
+    };
+    if !project_dir.is_dir() {
+        return Err(eyre!(
+            "💥 Project directory doesn't exist: {}",
+            project_dir.display()
+        ));
+    }
+
+    let (t, by_id) = setup_template(app_state, &cmd.render).await?;
+    let project = render_project(app_state, &cmd.render, t, by_id, &project_dir).await?;
+
+    let result = tpl::plan_project(&project.work_dir, &project_dir).and_then(|plan| {
+        let plan = plan
+            .into_iter()
+            .filter(|e| e.action != tpl::PlanAction::Delete)
+            .collect::<Vec<_>>();
+
+        if cmd.render.dry_run {
+            print_plan(&project, &project_dir, &plan, &[], &cmd.render)?;
+            info!(
+                "Files marked overwrite already exist and would be handled with --conflict {}.",
+                cmd.conflict
+            );
+            return Ok(());
+        }
+
+        let report = tpl::apply_project(
+            &project.work_dir,
+            &project_dir,
+            &plan,
+            cmd.conflict,
+            project.interactive,
+        )?;
+        print_table(report);
+        info!("Template applied to: {}", project_dir.display());
+        Ok(())
+    });
+    remove_dir_if_exists(&project.work_dir)?;
+    result?;
+
+    if !post_new_hooks(&project.config).is_empty() {
+        info!("This template's post_new hooks aren't run by `boil apply`.");
+    }
+
+    Ok(())
+}
@@ -6,13 +6,15 @@ use git2::Repository;
 use tracing::{info, warn};
 
 use crate::{
+    constants::COMMON_DIR,
     db::TemplateRow,
     state::AppState,
     template::{
-        CloneContext, InstallConfig, clone_repo, get_lang, get_template_config, install_template,
-        make_name_from_url, make_tmp_dir_from_url, remove_other_langs,
+        CloneContext, InstallConfig, RenderRules, clone_repo, get_lang, get_template_config,
+        head_commit_sha, install_template, make_name_from_url, make_tmp_dir_from_url,
+        remove_other_langs,
     },
-    util::file::{clean_dir, remove_git_dir},
+    util::file::{clean_dir, remove_dir_if_exists, remove_git_dir},
 };
 
 #[derive(Debug, Parser)]
@@ -21,8 +23,8 @@ pub struct Install {
     pub template: String,
     #[arg(short = 'n', long, help = "Rename")]
     pub rename: Option<String>,
-    #[arg(short, long)]
-    pub lang: Option<String>,
+    #[arg(short, long = "lang", help = "Language to install (repeatable)")]
+    pub langs: Vec<String>,
     #[arg(short, long)]
     pub branch: Option<String>,
     #[arg(short = 'd', long)]
@@ -59,8 +61,10 @@ async fn clone_remote_to_local_work_dir(
     Ok((repo, dir))
 }
 
+/// Clone the template and configure an install for each language. Every language shares the
+/// clone in `work_dir`.
 #[tracing::instrument]
-async fn configure_install(cmd: &Install) -> Result<InstallConfig> {
+async fn configure_install(cmd: &Install) -> Result<Vec<InstallConfig>> {
     let name = if let Some(name) = &cmd.rename {
         name.to_owned()
     } else {
@@ -71,7 +75,16 @@ async fn configure_install(cmd: &Install) -> Result<InstallConfig> {
     let (repo, work_dir) = clone_remote_to_local_work_dir(&repo_ctx, cmd).await?;
 
     let cnf = get_template_config(work_dir.as_path())?;
-    let lang = get_lang(&cnf, &cmd.lang)?;
+    let langs = if cmd.langs.is_empty() {
+        vec![get_lang(&cnf, &None)?]
+    } else {
+        cmd.langs.clone()
+    };
+    if langs.iter().any(|lang| lang == COMMON_DIR) {
+        return Err(eyre!(
+            "💥 {COMMON_DIR} holds files shared by every language and can't be installed as one."
+        ));
+    }
 
     let branch = if let Some(branch) = &cmd.branch {
         branch.to_owned()
@@ -81,43 +94,36 @@ async fn configure_install(cmd: &Install) -> Result<InstallConfig> {
 
     let subdir = cmd.subdir.to_owned();
 
-    let mut install = InstallConfig {
-        name,
-        lang,
-        repo: repo_ctx.url.to_owned(),
-        branch,
-        subdir,
-        work_dir,
-        sha256_hash: None,
-        template_dir: None,
-    };
-    install.set_hash_string();
-    install.set_template_dir();
-
-    Ok(install)
+    let commit_sha = head_commit_sha(&repo);
+    let installs = langs
+        .into_iter()
+        .map(|lang| {
+            let mut install = InstallConfig {
+                name: name.clone(),
+                lang,
+                repo: repo_ctx.url.to_owned(),
+                branch: branch.clone(),
+                subdir: subdir.clone(),
+                work_dir: work_dir.clone(),
+                sha256_hash: None,
+                template_dir: None,
+                commit_sha: commit_sha.clone(),
+            };
+            install.set_hash_string();
+            install.set_template_dir();
+            install
+        })
+        .collect();
+
+    Ok(installs)
 }
 
 // TODO: add default_branch, default_subdir to config
 #[tracing::instrument]
 pub async fn install(app_state: &AppState, cmd: &Install) -> Result<()> {
-    let install = configure_install(cmd).await?;
-    let template_dir = install.template_dir.clone().unwrap();
-    // TODO: clean up InstallConfig + TemplateRow duplication
-    let row = TemplateRow {
-        name: install.name.to_owned(),
-        lang: install.lang.to_owned(),
-        repo: install.repo.to_owned(),
-        branch: Some(install.branch.to_owned()),
-        subdir: install.subdir.to_owned(),
-        sha256_hash: Some(install.sha256_hash.to_owned().unwrap()),
-        template_dir: install
-            .template_dir
-            .clone()
-            .unwrap()
-            .into_os_string()
-            .into_string()
-            .unwrap(),
-    };
+    let installs = configure_install(cmd).await?;
+    let langs = installs.iter().map(|i| i.lang.clone()).collect::<Vec<_>>();
+    let work_dir = installs[0].work_dir.clone();
 
     let db = app_state.local_db.clone();
 
@@ -125,49 +131,75 @@ pub async fn install(app_state: &AppState, cmd: &Install) -> Result<()> {
         db.create_schema().await?;
     }
 
-    let existing_db_entry = db.check_unique(&row).await?;
-
-    if let Some(t) = existing_db_entry {
-        if template_dir.exists() {
-            return Err(eyre!(
-                "💥 Template with the same name/lang/repo already exists: {}, {}, {}",
-                t.name,
-                t.lang,
-                t.repo
-            ));
-        } else {
-            info!(
-                "Template entry exists in DB but directory is missing. Reininstalling: {}.",
-                t.name
-            );
-            db.delete_template(t.id).await?;
+    // Check every language before installing any, so a conflict doesn't leave a partial install.
+    let mut rows = Vec::with_capacity(installs.len());
+    for install in &installs {
+        let template_dir = install.template_dir.clone().unwrap();
+        // TODO: clean up InstallConfig + TemplateRow duplication
+        let row = TemplateRow {
+            name: install.name.to_owned(),
+            lang: install.lang.to_owned(),
+            repo: install.repo.to_owned(),
+            branch: Some(install.branch.to_owned()),
+            subdir: install.subdir.to_owned(),
+            sha256_hash: Some(install.sha256_hash.to_owned().unwrap()),
+            commit_sha: install.commit_sha.to_owned(),
+            template_dir: template_dir.clone().into_os_string().into_string().unwrap(),
+        };
+
+        let existing_db_entry = db.check_unique(&row).await?;
+
+        if let Some(t) = existing_db_entry {
+            if template_dir.exists() {
+                return Err(eyre!(
+                    "💥 Template with the same name/lang/repo already exists: {}, {}, {}",
+                    t.name,
+                    t.lang,
+                    t.repo
+                ));
+            } else {
+                info!(
+                    "Template entry exists in DB but directory is missing. Reininstalling: {}.",
+                    t.name
+                );
+                db.delete_template(t.id).await?;
+            }
         }
-    }
-
-    let new_id = db.create_template(row).await?;
 
-    info!("Template added to db with ID: {}", new_id);
+        rows.push(row);
+    }
 
     if !cmd.local {
-        remove_other_langs(&install)?;
+        remove_other_langs(&work_dir, &langs)?;
         // TODO: rm .gitignore from install dir
     }
 
-    match install_template(&install.work_dir, &template_dir).await {
-        Ok(_) => info!(
-            "Template installed successfully to: {}",
-            template_dir.display()
-        ),
-        Err(e) => {
-            return Err(eyre!("💥 Failed to install template: {}", e));
+    for (install, row) in installs.iter().zip(rows) {
+        let template_dir = install.template_dir.clone().unwrap();
+        let new_id = db.create_template(row).await?;
+
+        info!("Template added to db with ID: {}", new_id);
+
+        let rules = RenderRules::load(&install.work_dir, &install.lang)?;
+        match install_template(&install.work_dir, &template_dir, &rules).await {
+            Ok(_) => info!(
+                "Template installed successfully to: {}",
+                template_dir.display()
+            ),
+            Err(e) => {
+                return Err(eyre!("💥 Failed to install template: {}", e));
+            }
+        }
+        // Each language gets its own template dir, without the others installed alongside it.
+        for other in langs.iter().filter(|l| **l != install.lang) {
+            remove_dir_if_exists(&template_dir.join(other))?;
         }
-    }
 
-    db.index_template(new_id).await?;
+        db.index_template(new_id).await?;
+    }
 
     if !cmd.local {
-        let path = install.work_dir.clone();
-        remove_git_dir(&path)?;
+        remove_git_dir(&work_dir)?;
     }
 
     Ok(())
@@ -1,5 +1,6 @@
 use tabled::Tabled;
 
+pub mod apply;
 pub mod completion;
 pub mod config;
 pub mod docs;
@@ -13,7 +14,9 @@ pub mod show;
 pub mod sources;
 pub mod test;
 pub mod update;
+pub mod upgrade;
 
+pub use apply::{Apply, apply};
 pub use completion::Completion;
 pub use config::Config;
 pub use docs::Docs;
@@ -26,6 +29,7 @@ pub use search::{Search, search};
 pub use show::{Show, show};
 pub use sources::Sources;
 pub use update::{Update, update};
+pub use upgrade::{Upgrade, upgrade};
 
 // TODO: rename to TemplateOutputTableRow
 #[derive(Debug, Tabled)]
@@ -1,64 +1,179 @@
 use std::{
     collections::{HashMap, HashSet},
-    path::PathBuf,
+    fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
 };
 
-use clap::Parser;
+use clap::{Args, Parser};
 use color_eyre::{Result, eyre::eyre};
-use minijinja::{
-    context,
-    value::{Value as JinjaValue, merge_maps},
-};
+use minijinja::{context, value::Value as JinjaValue};
 use serde::Deserialize;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
 use crate::{
-    db::{TemplateFindParams, TemplateResult},
+    config::{TemplateConfig, TemplateConfigLayer},
+    constants::PROFILES_VAR,
+    db::{SourceTemplateFindParams, TemplateFindParams, TemplateResult},
     state::AppState,
     template as tpl,
+    template::context as ctx_util,
     util::{
-        file::{copy_dir, create_work_dir_clean, move_file},
+        archive::{OutputFormat, write_archive},
+        file::{
+            copy_dir, copy_missing, copy_path, create_work_dir_clean, move_file,
+            remove_dir_if_exists,
+        },
         help,
+        output::print_table,
     },
 };
 
-#[derive(Debug, Parser)]
-pub struct New {
+/// Args shared by the commands that render a template (`new`, `apply`).
+#[derive(Debug, Args)]
+pub struct RenderArgs {
     #[arg(required = true)]
     pub id_or_name: String,
-    #[arg(short, long)]
-    pub lang: Option<String>,
-    #[arg(short = 'n', long, help = "Rename")]
-    pub rename: Option<String>,
-    #[arg(short, long)]
-    pub dir: Option<String>,
+    #[arg(
+        short,
+        long = "lang",
+        value_name = "LANG[=DIR]",
+        help = "Language to generate (repeatable). With more than one, each goes in its own subdir"
+    )]
+    pub langs: Vec<String>,
     #[arg(short = 'p', long = "use-profile", value_name = "PROFILE")]
     pub use_profile: Option<String>,
     #[arg(short = 'v', long = "var", value_name = "KEY=VALUE")]
     pub vars: Vec<String>,
-    #[arg(short = 'O', long, default_value_t = false)]
-    pub overwrite: bool,
+    #[arg(
+        short = 'f',
+        long = "vars-file",
+        value_name = "FILE",
+        help = "TOML, JSON, YAML or .env file of vars (repeatable)"
+    )]
+    pub vars_files: Vec<PathBuf>,
     #[arg(short = 'S', long = "strict-vars", default_value_t = false)]
     pub strict_vars: bool,
-    #[arg(short = 'D', long, default_value_t = false)]
+    #[arg(
+        long = "no-input",
+        default_value_t = false,
+        help = "Don't prompt for vars"
+    )]
+    pub no_input: bool,
+    #[arg(
+        long = "allow-hooks",
+        default_value_t = false,
+        help = "Run template hooks without asking"
+    )]
+    pub allow_hooks: bool,
+    #[arg(
+        long = "dry-run",
+        default_value_t = false,
+        help = "Show the context and files that would be generated, without writing anything or running hooks"
+    )]
+    pub dry_run: bool,
+    #[arg(
+        long,
+        default_value_t = false,
+        requires = "dry_run",
+        help = "With --dry-run, diff against the existing project dir"
+    )]
+    pub diff: bool,
+    #[arg(
+        short = 'D',
+        long,
+        default_value_t = false,
+        help = "Log rendering details and how long each file took to render"
+    )]
     pub debug: bool,
 }
 
+#[derive(Debug, Parser)]
+pub struct New {
+    #[command(flatten)]
+    pub render: RenderArgs,
+    #[arg(short = 'n', long, help = "Rename")]
+    pub rename: Option<String>,
+    #[arg(short, long)]
+    pub dir: Option<String>,
+    #[arg(short = 'O', long, default_value_t = false)]
+    pub overwrite: bool,
+    #[arg(
+        long = "output-format",
+        value_name = "FORMAT",
+        conflicts_with = "dry_run",
+        help = "Write the project as a dir (default) or an archive"
+    )]
+    pub output_format: Option<OutputFormat>,
+    #[arg(
+        short = 'o',
+        long,
+        value_name = "FILE",
+        conflicts_with = "dry_run",
+        help = "Archive file to write, or - for stdout"
+    )]
+    pub output: Option<String>,
+}
+
+impl New {
+    /// Whether the project is streamed to stdout (`--output -`), so nothing else may print there.
+    pub fn writes_to_stdout(&self) -> bool {
+        self.output.as_deref() == Some("-")
+    }
+}
+
+/// A template rendered into a work dir, ready to be moved or applied to a project dir.
+#[derive(Debug)]
+pub struct RenderedProject {
+    pub template: TemplateResult,
+    pub by_id: bool,
+    pub config: TemplateConfig,
+    pub ctx: JinjaValue,
+    pub work_dir: PathBuf,
+    pub interactive: bool,
+    /// The languages and their subdirs, when more than one was rendered.
+    pub langs: Vec<tpl::ProjectLang>,
+}
+
+/// An installed template to render into `dir` of the project (the root when `None`).
+#[derive(Debug)]
+struct LangTarget {
+    template: TemplateResult,
+    dir: Option<String>,
+}
+
+/// A template rendered into the project: the one being generated, or one it pulls in with
+/// `[[extends]]` or `[[layers]]`.
+#[derive(Debug)]
+pub struct Layer {
+    pub base_dir: PathBuf,
+    pub lang: String,
+    pub config: TemplateConfig,
+    /// The `vars` set for it by the templates that pulled it in, outermost first.
+    pub vars: Vec<JinjaValue>,
+    /// Whether it's the template being generated.
+    pub is_root: bool,
+}
+
+/// The installed template to render, and whether it was picked by ID.
 #[tracing::instrument]
-async fn setup_template(app_state: &AppState, cmd: &New) -> Result<(TemplateResult, bool)> {
-    match cmd.id_or_name.parse::<i64>() {
+pub async fn setup_template(
+    app_state: &AppState,
+    args: &RenderArgs,
+) -> Result<(TemplateResult, bool)> {
+    match args.id_or_name.parse::<i64>() {
         Ok(id) => Ok((get_template_by_id(app_state, id).await?, true)),
         Err(_) => {
-            let existing_templates = get_existing_templates(app_state, cmd).await?;
+            let existing_templates = get_existing_templates(app_state, args).await?;
 
             match existing_templates.len() {
-                0 => Err(eyre!("💥 Cannot find template: {}.", cmd.id_or_name))?,
+                0 => Err(eyre!("💥 Cannot find template: {}.", args.id_or_name))?,
                 1 => Ok((existing_templates[0].to_owned(), false)),
                 2.. => {
                     help::print_multiple_template_results_help(&existing_templates);
                     Err(eyre!(
                         "💥 Found multiple results matching template: {}.",
-                        cmd.id_or_name
+                        args.id_or_name
                     ))?
                 }
             }
@@ -66,6 +181,103 @@ async fn setup_template(app_state: &AppState, cmd: &New) -> Result<(TemplateResu
     }
 }
 
+/// The `--lang` args as (lang, dir) pairs.
+#[tracing::instrument]
+fn lang_specs(args: &RenderArgs) -> Result<Vec<(String, Option<String>)>> {
+    let mut specs: Vec<(String, Option<String>)> = Vec::new();
+    for arg in &args.langs {
+        let (lang, dir) = match arg.split_once('=') {
+            Some((lang, dir)) => (lang.trim(), Some(dir.trim().to_owned())),
+            None => (arg.trim(), None),
+        };
+        if specs.iter().any(|(l, _)| l == lang) {
+            return Err(eyre!("💥 --lang {lang} is given more than once."));
+        }
+        specs.push((lang.to_owned(), dir));
+    }
+    Ok(specs)
+}
+
+/// Resolve every language to render: `t` for its own language, and the installed template with
+/// the same name, repo, branch and subdir for each other `--lang`.
+#[tracing::instrument]
+async fn resolve_langs(
+    app_state: &AppState,
+    t: &TemplateResult,
+    tpl_config: &TemplateConfig,
+    args: &RenderArgs,
+) -> Result<Vec<LangTarget>> {
+    let mut specs = lang_specs(args)?;
+    if !specs.iter().any(|(lang, _)| *lang == t.lang) {
+        specs.insert(0, (t.lang.clone(), None));
+    }
+    if specs.len() == 1 {
+        return Ok(vec![LangTarget {
+            template: t.clone(),
+            dir: None,
+        }]);
+    }
+
+    let mut targets: Vec<LangTarget> = Vec::with_capacity(specs.len());
+    for (lang, dir) in specs {
+        let dir = dir.unwrap_or_else(|| tpl_config.lang_dir(&lang));
+        if dir.is_empty()
+            || Path::new(&dir).is_absolute()
+            || dir.split(['/', '\\']).any(|c| c == "..")
+        {
+            return Err(eyre!(
+                "💥 Invalid dir for {lang}: {dir:?}. It must be a relative path inside the project."
+            ));
+        }
+        if let Some(other) = targets.iter().find(|o| o.dir.as_deref() == Some(&dir)) {
+            return Err(eyre!(
+                "💥 {lang} and {} would both be rendered into {dir}. (Use --lang {lang}=<dir>.)",
+                other.template.lang
+            ));
+        }
+        let template = if lang == t.lang {
+            t.clone()
+        } else {
+            get_sibling_template(app_state, t, &lang).await?
+        };
+        targets.push(LangTarget {
+            template,
+            dir: Some(dir),
+        });
+    }
+
+    Ok(targets)
+}
+
+/// The installed template for `lang` from the same template repo as `t`.
+#[tracing::instrument]
+pub async fn get_sibling_template(
+    app_state: &AppState,
+    t: &TemplateResult,
+    lang: &str,
+) -> Result<TemplateResult> {
+    let find_params = TemplateFindParams {
+        ids: None,
+        name: Some(t.name.clone()),
+        lang: Some(lang.to_owned()),
+        repo: Some(t.repo.clone()),
+        branch: t.branch.clone(),
+        subdir: t.subdir.clone(),
+        sha256_hash: None,
+    };
+
+    let db = app_state.local_db.clone();
+    db.find_templates(find_params)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(eyre!(
+            "💥 {} isn't installed for {lang}. Install it with `boil install {} --lang {lang}` first.",
+            t.name,
+            t.repo
+        ))
+}
+
 #[tracing::instrument]
 fn make_project_name(cmd: &New, t: &TemplateResult, by_id: bool) -> Result<String> {
     let project_name = if let Some(rename) = &cmd.rename {
@@ -73,60 +285,655 @@ fn make_project_name(cmd: &New, t: &TemplateResult, by_id: bool) -> Result<Strin
     } else if by_id {
         t.name.clone()
     } else {
-        cmd.id_or_name.to_string()
+        cmd.render.id_or_name.to_string()
     };
 
     Ok(project_name)
 }
 
-// Copies template from template_dir to temporary work_dir, renders it with context,
-// and if nothing fails, moves it to final project_dir.
-// TODO: refactor for readability (multiple functions?)
-// TODO: add --strict-vars flag to fail on unknown vars
+// Renders the template into a temporary work_dir and, if nothing fails, moves it to the final
+// project_dir.
 #[tracing::instrument]
 pub async fn new(app_state: &AppState, cmd: &New) -> Result<()> {
-    let (t, by_id) = setup_template(app_state, cmd).await?;
+    let format = output_format(cmd)?;
+    let (t, by_id) = setup_template(app_state, &cmd.render).await?;
+    let project_name = make_project_name(cmd, &t, by_id)?;
+    let project_dir = tpl::project_dir_path(&project_name, cmd.dir.as_deref())?;
+    let project = render_project(app_state, &cmd.render, t, by_id, &project_dir).await?;
+
+    if format != OutputFormat::Dir {
+        let result = write_project_archive(&project, &project_name, format, cmd);
+        remove_dir_if_exists(&project.work_dir)?;
+        return result;
+    }
+
+    if cmd.render.dry_run {
+        let result = tpl::plan_project(&project.work_dir, &project_dir).and_then(|plan| {
+            print_plan(
+                &project,
+                &project_dir,
+                &plan,
+                post_new_hooks(&project.config),
+                &cmd.render,
+            )
+        });
+        remove_dir_if_exists(&project.work_dir)?;
+        if project_dir.exists() && !cmd.overwrite {
+            warn!(
+                "Project directory already exists: {}. `boil new` will fail without --overwrite.",
+                project_dir.display()
+            );
+        }
+        return result;
+    }
+
+    // Written before the project dir is created, so a project is never left without one.
+    if let Err(e) = tpl::write_answers(
+        &project.work_dir,
+        &tpl::Answers::new(&project.template, &project.langs, &project.ctx),
+    ) {
+        remove_dir_if_exists(&project.work_dir)?;
+        return Err(e);
+    }
+
+    let project_dir =
+        tpl::create_project_dir(&project_name, cmd.dir.as_deref(), cmd.overwrite).await?;
+    move_file(&project.work_dir, &project_dir).await?;
+
+    info!("Project created at: {}", project_dir.display());
+
+    let post_new = post_new_hooks(&project.config);
+    if !post_new.is_empty()
+        && tpl::confirm_hooks(
+            "post_new",
+            post_new,
+            cmd.render.allow_hooks,
+            project.interactive,
+        )?
+        && let Err(e) = tpl::run_hooks("post_new", post_new, &project_dir, &project.ctx)
+    {
+        return Err(eyre!(
+            "{e}\nThe project was still created at: {}",
+            project_dir.display()
+        ));
+    }
+
+    info!("All set. Happy hacking! 🚀");
+
+    Ok(())
+}
+
+/// The format to write the project in: `--output-format`, or inferred from `--output`'s
+/// extension.
+#[tracing::instrument]
+fn output_format(cmd: &New) -> Result<OutputFormat> {
+    let format = match (cmd.output_format, cmd.output.as_deref()) {
+        (Some(format), _) => format,
+        (None, None) => OutputFormat::Dir,
+        (None, Some("-")) => {
+            return Err(eyre!(
+                "💥 Pass --output-format to write the project to stdout."
+            ));
+        }
+        (None, Some(path)) => OutputFormat::from_path(path).ok_or_else(|| {
+            eyre!("💥 Can't tell the archive format of {path}. (Pass --output-format.)")
+        })?,
+    };
+
+    if format == OutputFormat::Dir && cmd.output.is_some() {
+        return Err(eyre!(
+            "💥 --output is for archives. (Use --dir to choose where the project dir goes.)"
+        ));
+    }
+    if cmd.writes_to_stdout() && io::stdout().is_terminal() {
+        return Err(eyre!(
+            "💥 Refusing to write a {format} archive to a terminal. (Redirect or pipe stdout.)"
+        ));
+    }
+
+    Ok(format)
+}
+
+/// Write the rendered project, with its answers file, as an archive with a top-level
+/// `project_name` dir. Goes to `--output` (`-` for stdout), or `<project_name>.<format>` in
+/// `--dir` (or the current dir).
+#[tracing::instrument]
+fn write_project_archive(
+    project: &RenderedProject,
+    project_name: &str,
+    format: OutputFormat,
+    cmd: &New,
+) -> Result<()> {
+    tpl::write_answers(
+        &project.work_dir,
+        &tpl::Answers::new(&project.template, &project.langs, &project.ctx),
+    )?;
+
+    if !post_new_hooks(&project.config).is_empty() {
+        warn!("post_new hooks aren't run when writing an archive.");
+    }
+
+    if cmd.writes_to_stdout() {
+        return write_archive(&project.work_dir, project_name, format, io::stdout().lock());
+    }
+
+    let archive_path = match &cmd.output {
+        Some(path) => PathBuf::from(path),
+        None => tpl::project_dir_path(&format!("{project_name}.{format}"), cmd.dir.as_deref())?,
+    };
+    if archive_path.exists() && !cmd.overwrite {
+        return Err(eyre!(
+            "💥 File already exists: {}. (Use --overwrite to force.)",
+            archive_path.display()
+        ));
+    }
+    if let Some(parent) = archive_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let file = fs::File::create(&archive_path)
+        .map_err(|e| eyre!("💥 Failed to create {}: {e}", archive_path.display()))?;
+    write_archive(
+        &project.work_dir,
+        project_name,
+        format,
+        io::BufWriter::new(file),
+    )?;
+
+    info!("Project archive created at: {}", archive_path.display());
+
+    Ok(())
+}
+
+/// Resolve the template's context for a project in `project_dir`, then render it into a fresh
+/// work dir. With more than one `--lang`, each language is rendered into its own subdir with the
+/// same context.
+///
+/// Checks `[requires]` and `pre_new` hooks first. Nothing is written outside the work dir.
+#[tracing::instrument]
+pub async fn render_project(
+    app_state: &AppState,
+    args: &RenderArgs,
+    t: TemplateResult,
+    by_id: bool,
+    project_dir: &Path,
+) -> Result<RenderedProject> {
     let tpl_base_dir = PathBuf::from(&t.template_dir);
-    let tpl_dir = tpl_base_dir.join(&t.lang);
     let tpl_config = tpl::get_template_config(&tpl_base_dir)?;
+    let interactive = !args.no_input && io::stdin().is_terminal() && io::stdout().is_terminal();
+    let mut targets = Vec::new();
+    for target in resolve_langs(app_state, &t, &tpl_config, args).await? {
+        let base_dir = PathBuf::from(&target.template.template_dir);
+        let config = if target.template.id == t.id {
+            tpl_config.clone()
+        } else {
+            tpl::get_template_config(&base_dir)?
+        };
+        let layers = resolve_layers(app_state, &base_dir, &target.template.lang, &config).await?;
+        targets.push((target, layers));
+    }
 
-    let tmp_work_dir = create_work_dir_clean(t.sha256_hash.as_ref().unwrap())?;
-    copy_dir(&tpl_dir, &tmp_work_dir).await?;
+    check_prerequisites(&tpl_config, &tpl_base_dir, args, interactive)?;
 
-    let mut ctx = if let Some(vars) = &tpl_config.variables {
-        vars.clone()
-    } else {
-        context! {}
-    };
+    let mut ctx = layer_defaults(&targets[0].1);
 
-    if let Some(profile_name) = &cmd.use_profile {
-        let Ok(profile_ctx) = ctx.get_attr("profiles") else {
+    let merge_opts = tpl_config.merge.as_ref();
+
+    if let Some(profile_name) = &args.use_profile {
+        let profiles = ctx.get_attr(PROFILES_VAR).unwrap_or_default();
+        if profiles.is_undefined() {
             return Err(eyre!("Cannot find profiles key in template context"));
-        };
-        let Ok(profile_ctx) = profile_ctx.get_attr(profile_name) else {
+        }
+        let profile_ctx = profiles.get_attr(profile_name).unwrap_or_default();
+        if profile_ctx.is_undefined() {
             return Err(eyre!("Cannot find profile: {}", profile_name));
-        };
-        // TODO: discuss deep merge (not initially obvious in minijinja)
-        ctx = merge_maps(vec![ctx, profile_ctx]);
+        }
+        ctx = tpl::deep_merge(vec![ctx, profile_ctx], merge_opts);
     }
 
-    if let Some(user_ctx) = cmdline_vars_to_hashmap(&cmd.vars)? {
-        let from_paths = tpl::get_template_paths(&tpl_dir).await?;
-        ctx = extend_template_context(vec![ctx, user_ctx], &from_paths, cmd)?;
+    // User-provided layers, lowest precedence first: vars files (in order), then --var.
+    let mut user_ctxs: Vec<JinjaValue> = Vec::new();
+    for path in &args.vars_files {
+        let file_ctx = ctx_util::load_vars_file(path)?;
+        user_ctxs.push(tpl::coerce_to_defaults(
+            file_ctx,
+            &ctx,
+            tpl_config.schema.as_ref(),
+        )?);
+    }
+    if let Some(cmdline_ctx) = cmdline_vars_to_context(&args.vars)? {
+        user_ctxs.push(tpl::coerce_to_defaults(
+            cmdline_ctx,
+            &ctx,
+            tpl_config.schema.as_ref(),
+        )?);
+    }
+
+    if interactive {
+        let mut skip = StringSet::new();
+        for user_ctx in &user_ctxs {
+            skip.extend(deserialize_jinja_value_map(user_ctx)?.into_keys());
+        }
+        let answers = tpl::prompt_for_vars(
+            &ctx,
+            tpl_config.prompts.as_ref(),
+            tpl_config.schema.as_ref(),
+            &skip,
+        )?;
+        ctx = tpl::deep_merge(vec![ctx, answers], merge_opts);
+    }
+
+    let user_paths = user_ctxs
+        .iter()
+        .flat_map(ctx_util::leaf_paths)
+        .collect::<StringSet>();
+    if !user_ctxs.is_empty() {
+        let contexts = [vec![ctx], user_ctxs].concat();
+        ctx = extend_template_context(contexts, &tpl_config, args)?;
+    }
+
+    ctx = ctx_util::with_boil_namespace(ctx, project_dir, &tpl_config.project);
+    ctx = tpl::apply_computed_vars(
+        ctx,
+        &layer_computed(&targets[0].1),
+        &tpl_config.project,
+        &user_paths,
+    )?;
+
+    if let Some(schema) = &tpl_config.schema {
+        ctx = tpl::apply_var_schema(ctx, schema)?;
+    }
+
+    let tmp_work_dir = create_work_dir_clean(t.sha256_hash.as_ref().unwrap())?;
+    for (target, layers) in &targets {
+        let dest = match &target.dir {
+            Some(dir) => tmp_work_dir.join(dir),
+            None => tmp_work_dir.clone(),
+        };
+        fs::create_dir_all(&dest)?;
+        render_layers_into(layers, &ctx, &dest, args.strict_vars, args.debug).await?;
     }
 
-    if let Err(e) = tpl::render_template_files(&tmp_work_dir, ctx, cmd.debug).await {
+    let langs = targets
+        .into_iter()
+        .filter_map(|(target, _)| {
+            target.dir.map(|dir| tpl::ProjectLang {
+                lang: target.template.lang,
+                dir,
+            })
+        })
+        .collect();
+
+    Ok(RenderedProject {
+        template: t,
+        by_id,
+        config: tpl_config,
+        ctx,
+        work_dir: tmp_work_dir,
+        interactive,
+        langs,
+    })
+}
+
+/// Copy the `lang` dir of the template in `tpl_base_dir` into `work_dir`, plus any `_common`
+/// files the language doesn't have, and render it with the final `ctx`.
+#[tracing::instrument]
+pub async fn render_template_into(
+    tpl_base_dir: &Path,
+    lang: &str,
+    tpl_config: &TemplateConfig,
+    ctx: &JinjaValue,
+    work_dir: &PathBuf,
+    strict_vars: bool,
+    debug_render: bool,
+) -> Result<()> {
+    let tpl_dir = tpl_base_dir.join(lang);
+    copy_dir(&tpl_dir, work_dir).await?;
+
+    let rules = tpl::RenderRules::new(tpl_base_dir, lang, tpl_config)?;
+    if let Some(common_dir) = rules.common_dir() {
+        copy_missing(&common_dir, work_dir)?;
+    }
+    if let Err(e) = tpl::render_template_files(
+        &tpl_dir,
+        work_dir,
+        ctx.clone(),
+        &rules,
+        strict_vars,
+        debug_render,
+    )
+    .await
+    {
         return Err(eyre!("💥 Failed to render template files: {e}"));
     }
 
-    let project_name = make_project_name(cmd, &t, by_id)?;
+    Ok(())
+}
 
-    let project_dir =
-        tpl::create_project_dir(&project_name, cmd.dir.as_deref(), cmd.overwrite).await?;
-    move_file(&tmp_work_dir, &project_dir).await?;
+/// The templates to render for the template in `base_dir`, bottom to top: each `[[extends]]`
+/// (with the templates it pulls in), the template itself, then each `[[layers]]`.
+#[tracing::instrument]
+pub async fn resolve_layers(
+    app_state: &AppState,
+    base_dir: &Path,
+    lang: &str,
+    config: &TemplateConfig,
+) -> Result<Vec<Layer>> {
+    let mut chain = vec![(base_dir.to_path_buf(), config.project.name.clone())];
+    collect_layers(app_state, base_dir, lang, config, Vec::new(), &mut chain).await
+}
 
-    info!("Project created at: {}", project_dir.display());
-    info!("All set. Happy hacking! 🚀");
+async fn collect_layers(
+    app_state: &AppState,
+    base_dir: &Path,
+    lang: &str,
+    config: &TemplateConfig,
+    vars: Vec<JinjaValue>,
+    chain: &mut Vec<(PathBuf, String)>,
+) -> Result<Vec<Layer>> {
+    let mut layers = Vec::new();
+    let extends = config.extends.as_deref().unwrap_or_default();
+    let on_top = config.layers.as_deref().unwrap_or_default();
+
+    for (i, entry) in extends.iter().chain(on_top).enumerate() {
+        if i == extends.len() {
+            layers.push(Layer {
+                base_dir: base_dir.to_path_buf(),
+                lang: lang.to_owned(),
+                config: config.clone(),
+                vars: vars.clone(),
+                is_root: chain.len() == 1,
+            });
+        }
+
+        let t = find_layer_template(app_state, entry, lang).await?;
+        let dep_dir = PathBuf::from(&t.template_dir);
+        if chain.iter().any(|(dir, _)| *dir == dep_dir) {
+            let names = chain
+                .iter()
+                .map(|(_, name)| name.as_str())
+                .collect::<Vec<_>>();
+            return Err(eyre!(
+                "💥 Templates pull each other in a loop: {} → {}",
+                names.join(" → "),
+                t.name
+            ));
+        }
+        let dep_config = tpl::get_template_config(&dep_dir)?;
+        let mut dep_vars = vars.clone();
+        dep_vars.extend(entry.vars.clone());
+
+        chain.push((dep_dir.clone(), t.name.clone()));
+        let dep_layers = Box::pin(collect_layers(
+            app_state,
+            &dep_dir,
+            &t.lang,
+            &dep_config,
+            dep_vars,
+            chain,
+        ))
+        .await?;
+        chain.pop();
+        layers.extend(dep_layers);
+    }
+
+    if on_top.is_empty() {
+        layers.push(Layer {
+            base_dir: base_dir.to_path_buf(),
+            lang: lang.to_owned(),
+            config: config.clone(),
+            vars,
+            is_root: chain.len() == 1,
+        });
+    }
+
+    Ok(layers)
+}
+
+/// The installed template an `[[extends]]`/`[[layers]]` entry refers to, in its `lang` or
+/// `lang` by default.
+#[tracing::instrument]
+async fn find_layer_template(
+    app_state: &AppState,
+    entry: &TemplateConfigLayer,
+    lang: &str,
+) -> Result<TemplateResult> {
+    let lang = entry.lang.clone().unwrap_or_else(|| lang.to_owned());
+    let db = app_state.local_db.clone();
+    let found = db
+        .find_templates(TemplateFindParams {
+            ids: None,
+            name: Some(entry.template.clone()),
+            lang: Some(lang.clone()),
+            repo: entry.repo.clone(),
+            branch: None,
+            subdir: None,
+            sha256_hash: None,
+        })
+        .await?;
+
+    match found.len() {
+        1 => Ok(found[0].to_owned()),
+        0 => {
+            let sources = db
+                .find_source_templates(SourceTemplateFindParams {
+                    ids: None,
+                    source_ids: None,
+                    name: Some(entry.template.clone()),
+                    lang: Some(lang.clone()),
+                    repo: entry.repo.clone(),
+                    branch: None,
+                    subdir: None,
+                    sha256_hash: None,
+                })
+                .await?;
+            match sources.first() {
+                Some(st) => Err(eyre!(
+                    "💥 This template uses {} ({lang}), which isn't installed. Install it with `boil sources templates install {}` first.",
+                    entry.template,
+                    st.id
+                )),
+                None => Err(eyre!(
+                    "💥 This template uses {} ({lang}), which isn't installed or in any source.",
+                    entry.template
+                )),
+            }
+        }
+        2.. => {
+            help::print_multiple_template_results_help(&found);
+            Err(eyre!(
+                "💥 Found multiple installed templates matching {} ({lang}). Set `repo` to pick one.",
+                entry.template
+            ))
+        }
+    }
+}
+
+/// The default vars of every layer, with the generated template's winning over the ones it
+/// pulls in, and theirs in order.
+pub fn layer_defaults(layers: &[Layer]) -> JinjaValue {
+    merge_layer_vars(layers, |config| config.variables.clone())
+}
+
+/// The `[computed]` vars of every layer, merged like `layer_defaults`.
+pub fn layer_computed(layers: &[Layer]) -> JinjaValue {
+    merge_layer_vars(layers, |config| config.computed.clone())
+}
+
+fn merge_layer_vars(
+    layers: &[Layer],
+    vars: impl Fn(&TemplateConfig) -> Option<JinjaValue>,
+) -> JinjaValue {
+    let (root, deps): (Vec<_>, Vec<_>) = layers.iter().partition(|layer| layer.is_root);
+    let tables = deps
+        .into_iter()
+        .chain(root)
+        .filter_map(|layer| vars(&layer.config))
+        .collect::<Vec<_>>();
+    tpl::deep_merge([vec![context! {}], tables].concat(), None)
+}
+
+/// Render each layer with `ctx` (plus its `vars`) and lay them over each other in `work_dir`, so
+/// a later layer's file replaces an earlier one's at the same path.
+#[tracing::instrument(skip(layers))]
+pub async fn render_layers_into(
+    layers: &[Layer],
+    ctx: &JinjaValue,
+    work_dir: &PathBuf,
+    strict_vars: bool,
+    debug_render: bool,
+) -> Result<()> {
+    if let [layer] = layers {
+        return render_template_into(
+            &layer.base_dir,
+            &layer.lang,
+            &layer.config,
+            ctx,
+            work_dir,
+            strict_vars,
+            debug_render,
+        )
+        .await;
+    }
+
+    let layers_dir = create_work_dir_clean(&format!("layers-{}", std::process::id()))?;
+    let mut result = Ok(());
+    for (i, layer) in layers.iter().enumerate() {
+        let layer_dir = layers_dir.join(i.to_string());
+        let layer_ctx = tpl::deep_merge([vec![ctx.clone()], layer.vars.clone()].concat(), None);
+        result = async {
+            fs::create_dir_all(&layer_dir)?;
+            render_template_into(
+                &layer.base_dir,
+                &layer.lang,
+                &layer.config,
+                &layer_ctx,
+                &layer_dir,
+                strict_vars,
+                debug_render,
+            )
+            .await?;
+            copy_path(&layer_dir, work_dir)
+        }
+        .await
+        .map_err(|e| {
+            eyre!(
+                "💥 Failed to render layer {}: {e}",
+                layer.config.project.name
+            )
+        });
+        if result.is_err() {
+            break;
+        }
+    }
+    remove_dir_if_exists(&layers_dir)?;
+
+    result
+}
+
+/// The template's `pre_new` hooks, if any.
+pub fn pre_new_hooks(tpl_config: &TemplateConfig) -> &[String] {
+    tpl_config
+        .hooks
+        .as_ref()
+        .map(|h| h.pre_new.as_slice())
+        .unwrap_or_default()
+}
+
+/// The template's `post_new` hooks, if any.
+pub fn post_new_hooks(tpl_config: &TemplateConfig) -> &[String] {
+    tpl_config
+        .hooks
+        .as_ref()
+        .map(|h| h.post_new.as_slice())
+        .unwrap_or_default()
+}
+
+/// Check `[requires]` and run the `pre_new` check hooks, failing with one report of everything
+/// that's missing. With `--dry-run` the hooks aren't run; `print_plan` lists them instead.
+#[tracing::instrument]
+fn check_prerequisites(
+    tpl_config: &TemplateConfig,
+    tpl_base_dir: &Path,
+    args: &RenderArgs,
+    interactive: bool,
+) -> Result<()> {
+    let mut problems = tpl_config
+        .requires
+        .as_ref()
+        .map(tpl::check_requirements)
+        .unwrap_or_default();
+
+    let pre_new = pre_new_hooks(tpl_config);
+    if !pre_new.is_empty() && !args.dry_run {
+        if tpl::confirm_hooks("pre_new", pre_new, args.allow_hooks, interactive)? {
+            problems.extend(tpl::run_check_hooks("pre_new", pre_new, tpl_base_dir));
+        } else {
+            problems.push("pre_new checks not run (pass --allow-hooks to run them)".to_owned());
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(eyre!(
+            "💥 This template's requirements aren't met:\n  - {}",
+            problems.join("\n  - ")
+        ));
+    }
+
+    Ok(())
+}
+
+/// `--dry-run`: print the resolved context, the `plan` for `project_dir`, the `pre_new` hooks that
+/// were skipped and the post_new `hooks` that would run, plus a diff with `--diff`.
+#[tracing::instrument(skip(plan))]
+pub fn print_plan(
+    project: &RenderedProject,
+    project_dir: &Path,
+    plan: &[tpl::PlanEntry],
+    hooks: &[String],
+    args: &RenderArgs,
+) -> Result<()> {
+    println!(
+        "Resolved context:\n{}",
+        serde_json::to_string_pretty(&project.ctx)?
+    );
+
+    println!("\nPlan for {}:", project_dir.display());
+    print_table(plan);
+
+    let summary = [
+        (tpl::PlanAction::Create, "to create"),
+        (tpl::PlanAction::Overwrite, "to overwrite"),
+        (tpl::PlanAction::Unchanged, "unchanged"),
+        (tpl::PlanAction::Delete, "to delete"),
+    ]
+    .into_iter()
+    .filter_map(|(action, label)| {
+        let n = plan.iter().filter(|e| e.action == action).count();
+        (n > 0).then(|| format!("{n} {label}"))
+    })
+    .collect::<Vec<_>>();
+    println!("\n{}", summary.join(", "));
+
+    let pre_new = pre_new_hooks(&project.config);
+    if !pre_new.is_empty() {
+        println!(
+            "\npre_new hooks (not run in a dry run):\n  $ {}",
+            pre_new.join("\n  $ ")
+        );
+    }
+    if !hooks.is_empty() {
+        println!("\npost_new hooks:\n  $ {}", hooks.join("\n  $ "));
+    }
+
+    if args.diff {
+        println!(
+            "\n{}",
+            tpl::diff_project(&project.work_dir, project_dir, plan)?
+        );
+    }
+
+    info!("Dry run: nothing was written.");
 
     Ok(())
 }
@@ -142,11 +949,14 @@ async fn get_template_by_id(app_state: &AppState, id: i64) -> Result<TemplateRes
 }
 
 #[tracing::instrument]
-async fn get_existing_templates(app_state: &AppState, cmd: &New) -> Result<Vec<TemplateResult>> {
+async fn get_existing_templates(
+    app_state: &AppState,
+    args: &RenderArgs,
+) -> Result<Vec<TemplateResult>> {
     let find_params = TemplateFindParams {
         ids: None,
-        name: Some(cmd.id_or_name.to_owned()),
-        lang: cmd.lang.clone(),
+        name: Some(args.id_or_name.to_owned()),
+        lang: lang_specs(args)?.into_iter().next().map(|(lang, _)| lang),
         repo: None,
         branch: None,
         subdir: None,
@@ -159,29 +969,48 @@ async fn get_existing_templates(app_state: &AppState, cmd: &New) -> Result<Vec<T
     Ok(existing_templates)
 }
 
-/// Turn a vec like ["foo=bar", "baz=quux"] into a `HashMap<String, String>`.
-/// Note: aggregate types are not (yet) supported.
+/// Turn a vec like ["foo=bar", "db.port=5432", "features=[\"a\"]"] into a (nested) context.
+///
+/// Keys are dot-delimited paths. Values starting with `[`, `{` or `"` are parsed as JSON, then
+/// as a TOML value; anything else is kept as a string.
 #[tracing::instrument]
-fn cmdline_vars_to_hashmap(vars_vec: &[String]) -> Result<Option<JinjaValue>> {
-    let vars_map: Result<HashMap<String, String>> = vars_vec
-        .iter()
-        .map(|mapping| {
-            mapping
-                .split_once("=")
-                .map(|(x, y)| (x.to_owned(), y.to_owned()))
-                .ok_or(eyre!("💥 Invalid variable format: {mapping}"))
-        })
-        .collect();
+pub fn cmdline_vars_to_context(vars_vec: &[String]) -> Result<Option<JinjaValue>> {
+    if vars_vec.is_empty() {
+        return Ok(None);
+    }
 
-    match vars_map {
-        Err(e) => Err(eyre!("Failed to parse command line vars: {e}")),
-        Ok(vars_map) => {
-            if vars_map.is_empty() {
-                Ok(None)
-            } else {
-                let context = JinjaValue::from_serialize(vars_map);
-                Ok(Some(context))
-            }
+    let mut contexts = Vec::with_capacity(vars_vec.len());
+    for mapping in vars_vec {
+        let Some((path, raw)) = mapping.split_once("=") else {
+            return Err(eyre!(
+                "Failed to parse command line vars: 💥 Invalid variable format: {mapping}"
+            ));
+        };
+        let value = parse_cmdline_var_value(raw);
+        contexts.push(ctx_util::nest_at_path(path.trim(), value)?);
+    }
+
+    Ok(Some(tpl::deep_merge(contexts, None)))
+}
+
+/// A `--var` value: a JSON (or TOML) literal if it starts with `[`, `{` or `"` and parses as
+/// one, otherwise the raw string, e.g. `[WIP] api` or `{{x}}`.
+#[tracing::instrument]
+fn parse_cmdline_var_value(raw: &str) -> JinjaValue {
+    let trimmed = raw.trim();
+    if !trimmed.starts_with(['[', '{', '"']) {
+        return JinjaValue::from(raw);
+    }
+
+    if let Ok(v) = serde_json::from_str::<serde_json::Value>(trimmed) {
+        return JinjaValue::from_serialize(v);
+    }
+
+    match toml::from_str::<toml::Table>(&format!("v = {trimmed}")) {
+        Ok(t) => JinjaValue::from_serialize(&t["v"]),
+        Err(_) => {
+            debug!("--var value isn't valid JSON or TOML, so it's used as a string: {raw}");
+            JinjaValue::from(raw)
         }
     }
 }
@@ -190,34 +1019,40 @@ fn cmdline_vars_to_hashmap(vars_vec: &[String]) -> Result<Option<JinjaValue>> {
 type StringSet = HashSet<String>;
 
 /// Allows user-provided vars.
-/// Note: Only top-level vars are allowed to be passed (for now).
+/// User vars are compared as dot-delimited paths, e.g. `db.port`, against the template's vars.
 // TODO: add better error handling instead of propagating deserialization errors
 // TODO: Disuss when/where to use static analysis to enforce var checks.
 #[tracing::instrument]
 fn extend_template_context(
     contexts: Vec<JinjaValue>,
-    template_paths: &Vec<PathBuf>,
-    cmd: &New,
+    tpl_config: &TemplateConfig,
+    args: &RenderArgs,
 ) -> Result<JinjaValue> {
-    let strict_mode = cmd.strict_vars;
+    let strict_mode = args.strict_vars;
 
-    let config_map = deserialize_jinja_value_map(&contexts[0])?;
-    let config_vars = config_map.keys().cloned().collect::<StringSet>();
+    let config_ctx = &contexts[0];
+    let declared_vars = tpl_config.declared_var_names();
 
     let mut user_vars: StringSet = HashSet::new();
     for ctx in &contexts[1..] {
-        let m = deserialize_jinja_value_map(ctx)?;
-        let vars = m.keys().cloned().collect::<StringSet>();
-        user_vars.extend(vars);
+        user_vars.extend(ctx_util::leaf_paths(ctx));
     }
 
-    let is_config_superset = config_vars.is_superset(&user_vars);
-
+    // A path is known if the template's vars contain it, or if its top-level name is only
+    // declared in `[prompts]`/`[schema]` (no default to compare against).
     let unknown_vars = user_vars
-        .difference(&config_vars)
-        .cloned()
+        .into_iter()
+        .filter(|path| {
+            let top = path.split('.').next().unwrap_or_default();
+            let in_config = !ctx_util::value_at_path(config_ctx, path).is_undefined();
+            let declared_only = declared_vars.contains(top)
+                && ctx_util::value_at_path(config_ctx, top).is_undefined();
+            !(in_config || declared_only)
+        })
         .collect::<StringSet>();
 
+    let is_config_superset = unknown_vars.is_empty();
+
     if strict_mode {
         info!("🔒Strict vars mode enabled.");
 
@@ -228,15 +1063,8 @@ fn extend_template_context(
             ));
         }
 
-        // TODO: Figure out comparator for StringSet vs nested context to diff vars
-        // NOTE: Convert all config_vars (in map) to flattened dot-delimited paths?
-        /*
-        let file_refs = ana::get_minijinja_vars(template_paths)?;
-        println!(
-            ">>> file_refs: {} {file_refs:#?}",
-            std::any::type_name_of_val(&file_refs)
-        );
-         */
+        // NOTE: references in template files and paths are checked in render_template_files,
+        // once the context is final and excluded/conditional files have been pruned.
     } else if !is_config_superset {
         warn!(
             "Unknown user vars provided: {:?} (Set --strict-vars to enforce.)",
@@ -244,7 +1072,7 @@ fn extend_template_context(
         );
     }
 
-    Ok(merge_maps(contexts))
+    Ok(tpl::deep_merge(contexts, tpl_config.merge.as_ref()))
 }
 
 // TODO: add better erro handling instead of propagating deserialization errors
@@ -65,7 +65,7 @@ pub async fn install(app_state: &AppState, cmd: &Install) -> Result<()> {
     let cmd = CoreCmdInstall {
         template: st.repo,
         rename: Some(st.name),
-        lang: Some(st.lang),
+        langs: vec![st.lang],
         branch: st.branch,
         subdir: st.subdir,
         local: false,
@@ -7,7 +7,10 @@ use crate::db::TemplateRow;
 use crate::state::AppState;
 use crate::util::file::remove_git_dir;
 use crate::{
-    template::{CloneContext, clone_repo, install_template, make_tmp_dir_from_url},
+    template::{
+        CloneContext, RenderRules, clone_repo, head_commit_sha, install_template,
+        make_tmp_dir_from_url, remove_other_langs,
+    },
     util::file::clean_dir,
 };
 
@@ -42,12 +45,21 @@ pub async fn update(app_state: &AppState, cmd: &Update) -> Result<()> {
         Some(tmp_clone_dir.clone()),
         templ.branch.clone(),
     );
-    clone_repo(&clone_ctx).await?;
+    let repo = clone_repo(&clone_ctx).await?;
+    let commit_sha = head_commit_sha(&repo);
+    let src_dir = match &templ.subdir {
+        Some(subdir) => tmp_clone_dir.join(subdir),
+        None => tmp_clone_dir.clone(),
+    };
+    // Like `install`, keep only this template's language (and `_common`).
+    remove_other_langs(&src_dir, std::slice::from_ref(&templ.lang))?;
+    let rules = RenderRules::load(&src_dir, &templ.lang)?;
     clean_dir(&template_dir)?;
-    install_template(&tmp_clone_dir, &template_dir).await?;
+    install_template(&src_dir, &template_dir, &rules).await?;
     remove_git_dir(&template_dir)?;
 
-    let row = TemplateRow::from(templ.clone());
+    let mut row = TemplateRow::from(templ.clone());
+    row.commit_sha = commit_sha;
     cache.update_template(templ.id, row).await?;
 
     info!("✅ Template updated!");
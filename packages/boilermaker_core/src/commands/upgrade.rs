@@ -0,0 +1,256 @@
+use std::{
+    collections::HashSet,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use clap::Parser;
+use color_eyre::{Result, eyre::eyre};
+use minijinja::value::Value as JinjaValue;
+use tracing::{info, warn};
+
+use crate::{
+    commands::new::{
+        cmdline_vars_to_context, get_sibling_template, layer_computed, layer_defaults,
+        render_layers_into, resolve_layers,
+    },
+    constants::ANSWERS_FILE,
+    db::{TemplateFindParams, TemplateResult},
+    state::AppState,
+    template as tpl,
+    template::context::{leaf_paths, with_boil_namespace},
+    util::{
+        file::{create_work_dir_clean, remove_dir_if_exists},
+        output::print_table,
+    },
+};
+
+#[derive(Debug, Parser)]
+pub struct Upgrade {
+    #[arg(short, long, help = "Project dir to upgrade (default: current dir)")]
+    pub dir: Option<PathBuf>,
+    #[arg(
+        short = 'v',
+        long = "var",
+        value_name = "KEY=VALUE",
+        help = "Change an answer or set a new var"
+    )]
+    pub vars: Vec<String>,
+    #[arg(
+        long = "dry-run",
+        default_value_t = false,
+        help = "Show what would change without writing anything"
+    )]
+    pub dry_run: bool,
+    #[arg(short = 'D', long, default_value_t = false)]
+    pub debug: bool,
+}
+
+// Renders the template at the commit the project was generated from and at the installed
+// version, both with the project's answers, then merges the difference into the project.
+#[tracing::instrument]
+pub async fn upgrade(app_state: &AppState, cmd: &Upgrade) -> Result<()> {
+    let project_dir = match &cmd.dir {
+        Some(dir) => dir.clone(),
+        None => env::current_dir()?,
+    };
+    let answers = tpl::read_answers(&project_dir)?;
+    let Some(old_commit) = answers.template.commit.clone() else {
+        return Err(eyre!(
+            "💥 {ANSWERS_FILE} doesn't record which template commit the project was generated from."
+        ));
+    };
+
+    let t = get_installed_template(app_state, &answers).await?;
+    let Some(new_commit) = t.commit_sha.clone() else {
+        return Err(eyre!(
+            "💥 Installed template #{} doesn't record its commit. Run `boil update {}` first.",
+            t.id,
+            t.id
+        ));
+    };
+    if new_commit == old_commit && cmd.vars.is_empty() {
+        info!("Already up to date with {} at {new_commit}.", t.name);
+        return Ok(());
+    }
+
+    info!("Upgrading {} from {old_commit} to {new_commit}", t.name);
+
+    let work_dir = create_work_dir_clean(&format!(
+        "upgrade-{}",
+        t.sha256_hash.as_deref().unwrap_or(&t.name)
+    ))?;
+    let result = merge_upgrade(
+        app_state,
+        &t,
+        &answers,
+        &old_commit,
+        &project_dir,
+        &work_dir,
+        cmd,
+    )
+    .await;
+    remove_dir_if_exists(&work_dir)?;
+    let (report, new_ctx) = result?;
+
+    if report.is_empty() {
+        info!("The template changes don't affect any files.");
+    } else {
+        print_table(&report);
+    }
+
+    if cmd.dry_run {
+        info!("Dry run: nothing was written.");
+        return Ok(());
+    }
+
+    tpl::write_answers(
+        &project_dir,
+        &tpl::Answers::new(&t, &answers.template.langs, &new_ctx),
+    )?;
+
+    let conflicts = report.iter().filter(|e| e.result == "conflict").count();
+    if conflicts > 0 {
+        warn!(
+            "{conflicts} file(s) have conflicts. Resolve the <<<<<<< / >>>>>>> markers before committing."
+        );
+    } else {
+        info!("✅ Project upgraded!");
+    }
+
+    Ok(())
+}
+
+/// Render the old and new template versions into `work_dir` and merge them into `project_dir`.
+/// Returns the report and the new context.
+#[tracing::instrument]
+async fn merge_upgrade(
+    app_state: &AppState,
+    t: &TemplateResult,
+    answers: &tpl::Answers,
+    old_commit: &str,
+    project_dir: &Path,
+    work_dir: &Path,
+    cmd: &Upgrade,
+) -> Result<(Vec<tpl::ApplyEntry>, JinjaValue)> {
+    let lang_dirs = answers.lang_dirs();
+
+    let clone_dir = work_dir.join("repo");
+    let repo = tpl::clone_repo(&tpl::CloneContext::new(
+        &answers.template.repo,
+        Some(clone_dir.clone()),
+        answers.template.branch.clone(),
+    ))
+    .await?;
+    tpl::checkout_commit(&repo, old_commit)?;
+
+    let old_base_dir = match &answers.template.subdir {
+        Some(subdir) => clone_dir.join(subdir),
+        None => clone_dir,
+    };
+    let old_config = tpl::get_template_config(&old_base_dir)?;
+    // Templates pulled in with `[[extends]]`/`[[layers]]` are rendered from their installed
+    // versions on both sides, so only this template's changes are merged.
+    let mut old_layers = Vec::with_capacity(lang_dirs.len());
+    for (lang, _) in &lang_dirs {
+        old_layers.push(resolve_layers(app_state, &old_base_dir, lang, &old_config).await?);
+    }
+    // The answers are already a merged context, so they replace the defaults instead of being
+    // appended to them.
+    let old_ctx = tpl::deep_merge(
+        vec![layer_defaults(&old_layers[0]), answers.context.clone()],
+        None,
+    );
+    let old_ctx = with_boil_namespace(old_ctx, project_dir, &old_config.project);
+    let old_dir = work_dir.join("old");
+    for ((_, dir), layers) in lang_dirs.iter().zip(&old_layers) {
+        let dest = lang_dest(&old_dir, dir.as_deref());
+        fs::create_dir_all(&dest)?;
+        render_layers_into(layers, &old_ctx, &dest, false, cmd.debug).await?;
+    }
+
+    let new_config = tpl::get_template_config(&PathBuf::from(&t.template_dir))?;
+    let mut new_layers = Vec::with_capacity(lang_dirs.len());
+    for (lang, _) in &lang_dirs {
+        let sibling = if *lang == t.lang {
+            t.clone()
+        } else {
+            get_sibling_template(app_state, t, lang).await?
+        };
+        let base_dir = PathBuf::from(&sibling.template_dir);
+        let config = tpl::get_template_config(&base_dir)?;
+        new_layers.push(resolve_layers(app_state, &base_dir, lang, &config).await?);
+    }
+    let defaults = layer_defaults(&new_layers[0]);
+    let mut new_ctx = tpl::deep_merge(vec![defaults.clone(), answers.context.clone()], None);
+    let mut user_paths = HashSet::new();
+    if let Some(vars) = cmdline_vars_to_context(&cmd.vars)? {
+        let vars = tpl::coerce_to_defaults(vars, &defaults, new_config.schema.as_ref())?;
+        user_paths.extend(leaf_paths(&vars));
+        new_ctx = tpl::deep_merge(vec![new_ctx, vars], new_config.merge.as_ref());
+    }
+    new_ctx = with_boil_namespace(new_ctx, project_dir, &new_config.project);
+    // Computed vars follow the answers they're computed from, unless set with -v.
+    new_ctx = tpl::apply_computed_vars(
+        new_ctx,
+        &layer_computed(&new_layers[0]),
+        &new_config.project,
+        &user_paths,
+    )?;
+    if let Some(schema) = &new_config.schema {
+        new_ctx = tpl::apply_var_schema(new_ctx, schema)?;
+    }
+    let new_dir = work_dir.join("new");
+    for ((_, dir), layers) in lang_dirs.iter().zip(&new_layers) {
+        let dest = lang_dest(&new_dir, dir.as_deref());
+        fs::create_dir_all(&dest)?;
+        render_layers_into(layers, &new_ctx, &dest, false, cmd.debug).await?;
+    }
+
+    let report = tpl::merge_project(
+        &old_dir,
+        &new_dir,
+        project_dir,
+        &work_dir.join("merge.git"),
+        !cmd.dry_run,
+    )?;
+
+    Ok((report, new_ctx))
+}
+
+/// Where a language is rendered under `dir`: its subdir, or `dir` itself.
+fn lang_dest(dir: &Path, lang_dir: Option<&str>) -> PathBuf {
+    match lang_dir {
+        Some(lang_dir) => dir.join(lang_dir),
+        None => dir.to_path_buf(),
+    }
+}
+
+#[tracing::instrument]
+async fn get_installed_template(
+    app_state: &AppState,
+    answers: &tpl::Answers,
+) -> Result<TemplateResult> {
+    let at = &answers.template;
+    let find_params = TemplateFindParams {
+        ids: None,
+        name: Some(at.name.clone()),
+        lang: Some(at.lang.clone()),
+        repo: Some(at.repo.clone()),
+        branch: at.branch.clone(),
+        subdir: at.subdir.clone(),
+        sha256_hash: None,
+    };
+
+    let db = app_state.local_db.clone();
+    db.find_templates(find_params)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(eyre!(
+            "💥 Template isn't installed: {} ({}). Install it with `boil install {}` first.",
+            at.name,
+            at.repo,
+            at.repo
+        ))
+}
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs,
     fs::OpenOptions,
     path::{Path, PathBuf},
@@ -199,6 +199,48 @@ pub fn get_template_config(template_path: &Path) -> Result<TemplateConfig> {
 pub struct TemplateConfig {
     pub project: TemplateConfigProject,
     pub variables: Option<JinjaValue>,
+    pub computed: Option<JinjaValue>,
+    pub prompts: Option<HashMap<String, TemplateConfigPrompt>>,
+    pub schema: Option<HashMap<String, TemplateConfigVarSchema>>,
+    pub merge: Option<TemplateConfigMerge>,
+    pub render: Option<TemplateConfigRender>,
+    pub files: Option<Vec<TemplateConfigFile>>,
+    pub hooks: Option<TemplateConfigHooks>,
+    pub requires: Option<TemplateConfigRequires>,
+    pub syntax: Option<TemplateConfigSyntax>,
+    pub langs: Option<HashMap<String, TemplateConfigLang>>,
+    pub extends: Option<Vec<TemplateConfigLayer>>,
+    pub layers: Option<Vec<TemplateConfigLayer>>,
+}
+
+impl TemplateConfig {
+    /// The project subdir `lang` is rendered into when `boil new` generates more than one
+    /// language: `[langs.<lang>] dir`, or the language's name.
+    pub fn lang_dir(&self, lang: &str) -> String {
+        self.langs
+            .as_ref()
+            .and_then(|langs| langs.get(lang))
+            .and_then(|l| l.dir.clone())
+            .unwrap_or_else(|| lang.to_owned())
+    }
+
+    /// Names of all top-level variables the template declares, whether they have a default in
+    /// `[variables]`, are `[computed]` or are only described in `[prompts]`/`[schema]`.
+    pub fn declared_var_names(&self) -> HashSet<String> {
+        let mut names: HashSet<String> = HashSet::new();
+        for vars in [&self.variables, &self.computed].into_iter().flatten() {
+            if let Ok(keys) = vars.try_iter() {
+                names.extend(keys.filter_map(|k| k.as_str().map(str::to_owned)));
+            }
+        }
+        if let Some(prompts) = &self.prompts {
+            names.extend(prompts.keys().cloned());
+        }
+        if let Some(schema) = &self.schema {
+            names.extend(schema.keys().cloned());
+        }
+        names
+    }
 }
 
 // TODO: add all remaining fields
@@ -222,6 +264,153 @@ pub struct TemplateConfigProject {
     pub website: Option<String>,
 }
 
+/// Per-variable metadata used when asking for values interactively in `boil new`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TemplateConfigPrompt {
+    pub message: Option<String>,
+    pub help: Option<String>,
+}
+
+/// Controls which files are rendered (`[render]`). Globs are relative to the language dir.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TemplateConfigRender {
+    /// Files left out of generated projects (and the search index) entirely.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Files copied byte-for-byte, without rendering or indexing.
+    #[serde(default)]
+    pub copy_only: Vec<String>,
+    /// Text files written out verbatim, without rendering. Unlike `copy_only`, they're indexed.
+    #[serde(default)]
+    pub raw: Vec<String>,
+}
+
+/// A file or directory that's only generated when `when` is true (`[[files]]`).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TemplateConfigFile {
+    /// Path relative to the language dir, before file path interpolation.
+    pub path: String,
+    /// A minijinja expression evaluated against the final context, e.g. `db != "none"`.
+    pub when: String,
+}
+
+/// Prerequisites checked before `boil new` generates anything (`[requires]`).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TemplateConfigRequires {
+    /// Commands that must be on the `PATH`.
+    #[serde(default)]
+    pub commands: Vec<String>,
+    /// Minimum versions by command, compared against the output of `<command> --version`.
+    #[serde(default)]
+    pub min_versions: HashMap<String, String>,
+    /// Environment variables that must be set.
+    #[serde(default)]
+    pub env: Vec<String>,
+}
+
+/// Shell commands a template runs around generation (`[hooks]`). Never run without the user's
+/// confirmation or `--allow-hooks`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TemplateConfigHooks {
+    /// Checks run in the template dir before anything is generated. All must exit with 0.
+    #[serde(default)]
+    pub pre_new: Vec<String>,
+    /// Run in the project dir after it's been created.
+    #[serde(default)]
+    pub post_new: Vec<String>,
+}
+
+/// Jinja delimiters for the template (`[syntax]`), plus per-glob overrides.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TemplateConfigSyntax {
+    #[serde(flatten)]
+    pub delimiters: TemplateConfigDelimiters,
+    #[serde(default)]
+    pub overrides: Vec<TemplateConfigSyntaxOverride>,
+}
+
+/// Start/end delimiter pairs. Unset pairs keep the default (`{% %}`, `{{ }}`, `{# #}`).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TemplateConfigDelimiters {
+    pub block: Option<[String; 2]>,
+    pub variable: Option<[String; 2]>,
+    pub comment: Option<[String; 2]>,
+}
+
+/// Delimiters for files matching `paths` (`[[syntax.overrides]]`), on top of `[syntax]`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TemplateConfigSyntaxOverride {
+    /// Globs relative to the language dir.
+    pub paths: Vec<String>,
+    #[serde(flatten)]
+    pub delimiters: TemplateConfigDelimiters,
+}
+
+/// Per-language settings (`[langs.<lang>]`).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TemplateConfigLang {
+    /// The project subdir the language is rendered into alongside others, e.g. `client`.
+    pub dir: Option<String>,
+}
+
+/// Another installed template rendered into the same project: underneath this one
+/// (`[[extends]]`) or on top of it (`[[layers]]`).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TemplateConfigLayer {
+    /// Name of the installed template.
+    pub template: String,
+    /// The language to use. Defaults to the one being generated.
+    pub lang: Option<String>,
+    /// Picks between installed templates with the same name.
+    pub repo: Option<String>,
+    /// Vars for this template (and the ones it pulls in), over the project's context.
+    pub vars: Option<JinjaValue>,
+}
+
+/// Controls how variable layers (defaults, profile, CLI, ...) are merged (`[merge]`).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TemplateConfigMerge {
+    /// Dot-delimited paths of lists that are appended to instead of replaced.
+    #[serde(default)]
+    pub append: Vec<String>,
+}
+
+/// Declared type and constraints for a single variable (`[schema.NAME]`).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TemplateConfigVarSchema {
+    #[serde(rename = "type", default)]
+    pub var_type: TemplateConfigVarType,
+    #[serde(default)]
+    pub required: bool,
+    pub pattern: Option<String>,
+    pub choices: Option<Vec<JinjaValue>>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateConfigVarType {
+    #[default]
+    String,
+    Int,
+    Float,
+    Bool,
+    List,
+    Enum,
+}
+
+impl std::fmt::Display for TemplateConfigVarType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateConfigVarType::String => write!(f, "string"),
+            TemplateConfigVarType::Int => write!(f, "int"),
+            TemplateConfigVarType::Float => write!(f, "float"),
+            TemplateConfigVarType::Bool => write!(f, "bool"),
+            TemplateConfigVarType::List => write!(f, "list"),
+            TemplateConfigVarType::Enum => write!(f, "enum"),
+        }
+    }
+}
+
 pub fn expand_tilde(path: &str) -> Option<PathBuf> {
     if let Some(stripped) = path.strip_prefix("~/") {
         dirs::home_dir().map(|mut home| {
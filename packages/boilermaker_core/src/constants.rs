@@ -6,9 +6,19 @@ use regex::Regex;
 
 use crate::config::{get_template_base_dir, make_local_db_path};
 
+pub const ANSWERS_FILE: &str = ".boilermaker-answers.toml";
+pub const BOILIGNORE_FILE: &str = ".boilignore";
+/// Context key reserved for the built-in vars, e.g. `boil.year`.
+pub const BOIL_NAMESPACE: &str = "boil";
+/// Context key holding the template's named profiles, picked from with `--use-profile`.
+pub const PROFILES_VAR: &str = "profiles";
+/// Template root dir whose files are added to every language (the language's own files win).
+pub const COMMON_DIR: &str = "_common";
 pub const BRANCH_REGEX: &str = r"^(refs/heads/)?[A-Za-z0-9._/-]+$";
 pub const SUBDIR_REGEX: &str = r"^/?[A-Za-z0-9/\-_].*$";
 pub const TEMPLATE_FILEPATH_VAR_REGEX: &str = r"(?<underscore>___.*?___)|(?<dash>---.*?---)";
+pub const TEMPLATE_PATH_LOOP_REGEX: &str =
+    r"^(?:(?<expr>.+?)\s+for\s+)?(?<var>[A-Za-z_]\w*)\s+in\s+(?<list>.+)$";
 pub const URL_PREFIX_REGEX: &str = r"^(https?|git|ssh|ftp|ftps)://.*?/";
 
 lazy_static! {
@@ -39,5 +49,7 @@ lazy_static! {
         DEFAULT_TEMPLATE_DIR.as_path().to_str().unwrap().to_string();
     pub static ref TEMPLATE_FILEPATH_VAR_PATTERN: Regex =
         Regex::new(TEMPLATE_FILEPATH_VAR_REGEX).unwrap();
+    pub static ref TEMPLATE_PATH_LOOP_PATTERN: Regex =
+        Regex::new(TEMPLATE_PATH_LOOP_REGEX).unwrap();
     pub static ref URL_PREFIX_PATTERN: Regex = Regex::new(URL_PREFIX_REGEX).unwrap();
 }
@@ -12,7 +12,7 @@ use crate::{
     template as tmpl,
     template::{InstallableTemplate, make_name_from_url},
     util::crypto::sha256_hash_string,
-    util::file::read_file_to_string,
+    util::file::read_text_file,
 };
 
 use super::LocalDb;
@@ -141,8 +141,14 @@ impl SourceMethods for LocalDb {
 
             let repo_name_relative = make_name_from_url(&source_template_row.repo);
 
-            let files = tmpl::list_template_files(&path).await?;
+            let base_path = path.parent().unwrap_or(&path);
+            let rules = tmpl::RenderRules::load(base_path, &source_template_row.lang)?;
+            let files = tmpl::list_template_files(&path, &rules).await?;
             for file in files {
+                let Some(content) = read_text_file(&file)? else {
+                    continue;
+                };
+
                 let file_path = file.to_string_lossy().to_string();
                 let base_path_index = file_path.find(&repo_name_relative).unwrap();
                 let file_path = file_path
@@ -150,8 +156,6 @@ impl SourceMethods for LocalDb {
                     .1
                     .replace(&repo_name_relative, "");
 
-                let content = read_file_to_string(&file)?;
-
                 let _ = sqlx::query(
                     r#"
                     INSERT INTO source_template_content
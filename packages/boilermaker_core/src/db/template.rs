@@ -10,7 +10,7 @@ use crate::db::{SearchOptions, SearchResult, SearchResultKind};
 use crate::template as tmpl;
 use crate::template::InstallableTemplate;
 use crate::util::crypto::sha256_hash_string;
-use crate::util::file::read_file_to_string;
+use crate::util::file::read_text_file;
 use crate::util::output::strip_url_prefix;
 
 #[async_trait::async_trait]
@@ -63,9 +63,9 @@ impl TemplateMethods for LocalDb {
         let template_result = sqlx::query(
             r#"
             INSERT INTO template
-              (name, lang, template_dir, created_at, repo, branch, subdir, sha256_hash)
+              (name, lang, template_dir, created_at, repo, branch, subdir, sha256_hash, commit_sha)
             VALUES
-              (?, ?, ?, strftime('%s','now'), ?, ?, ?, ?);
+              (?, ?, ?, strftime('%s','now'), ?, ?, ?, ?, ?);
             "#,
         )
         .bind(&row.name)
@@ -75,6 +75,7 @@ impl TemplateMethods for LocalDb {
         .bind(&row.branch)
         .bind(&row.subdir)
         .bind(&row.sha256_hash)
+        .bind(&row.commit_sha)
         .execute(&self.pool)
         .await?;
 
@@ -162,9 +163,13 @@ impl TemplateMethods for LocalDb {
             .await?
             .ok_or_else(|| eyre!("Template with id {} not found", id))?;
 
-        let files = tmpl::list_template_files(&PathBuf::from(&t.template_dir)).await?;
+        let template_dir = PathBuf::from(&t.template_dir);
+        let rules = tmpl::RenderRules::load(&template_dir, &t.lang)?;
+        let files = tmpl::list_template_files(&template_dir, &rules).await?;
         for file in files {
-            let content = read_file_to_string(&file)?;
+            let Some(content) = read_text_file(&file)? else {
+                continue;
+            };
             let _ = sqlx::query(
                 r#"
                 INSERT INTO template_content
@@ -252,6 +257,7 @@ impl TemplateMethods for LocalDb {
                 branch = ?,
                 subdir = ?,
                 sha256_hash = ?,
+                commit_sha = ?,
                 updated_at = unixepoch()
             WHERE id = ?
             RETURNING id;
@@ -264,6 +270,7 @@ impl TemplateMethods for LocalDb {
         .bind(row.branch)
         .bind(row.subdir)
         .bind(row.sha256_hash)
+        .bind(row.commit_sha)
         .bind(id)
         .execute(&self.pool)
         .await?;
@@ -285,6 +292,7 @@ pub struct TemplateRow {
     pub branch: Option<String>,
     pub subdir: Option<String>,
     pub sha256_hash: Option<String>,
+    pub commit_sha: Option<String>,
 }
 
 impl TemplateRow {
@@ -321,6 +329,7 @@ impl From<TemplateResult> for TemplateRow {
             branch: value.branch,
             subdir: value.subdir,
             sha256_hash: value.sha256_hash,
+            commit_sha: value.commit_sha,
         }
     }
 }
@@ -337,6 +346,9 @@ pub struct TemplateResult {
     pub sha256_hash: Option<String>,
     pub created_at: Option<i32>,
     pub updated_at: Option<i32>,
+    /// The template repo's commit when it was installed or last updated.
+    #[sqlx(default)]
+    pub commit_sha: Option<String>,
 }
 
 impl InstallableTemplate for TemplateResult {
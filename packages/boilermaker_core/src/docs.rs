@@ -81,7 +81,7 @@ fn insert_doc_recursive(nodes: &mut Vec<DocTreeNode>, parts: &[&str], full_path:
                 .to_string();
 
             let name_pretty = name
-                .split(|c| c == '-' || c == '_')
+                .split(['-', '_'])
                 .map(|s| {
                     let mut c = s.chars();
                     match c.next() {
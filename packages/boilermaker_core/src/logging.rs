@@ -1,9 +1,12 @@
+use std::io;
+
 use color_eyre::eyre::Result;
 use nu_ansi_term::Color;
 use tracing::Level;
 use tracing_error::ErrorLayer;
 use tracing_subscriber::filter::EnvFilter;
 use tracing_subscriber::fmt;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::registry::LookupSpan;
@@ -45,12 +48,26 @@ where
 
 // TODO: allow debug! level
 #[tracing::instrument]
-pub fn init_tracing(debug_level: u8) -> Result<()> {
+pub fn init_tracing(debug_level: u8, to_stderr: bool) -> Result<()> {
+    // Logs go to stderr when stdout carries output, e.g. `boil new --output -`.
+    let writer = || {
+        if to_stderr {
+            BoxMakeWriter::new(io::stderr)
+        } else {
+            BoxMakeWriter::new(io::stdout)
+        }
+    };
+
     //TODO: Add more specific formatting for each debug level (0-4)
     let fmt_layer: Box<dyn tracing_subscriber::Layer<_> + Send + Sync> = match debug_level {
-        0 => Box::new(fmt::layer().event_format(DefaultFormatter)),
+        0 => Box::new(
+            fmt::layer()
+                .with_writer(writer())
+                .event_format(DefaultFormatter),
+        ),
         1 => Box::new(
             fmt::layer()
+                .with_writer(writer())
                 .event_format(fmt::format().compact())
                 .with_thread_ids(true)
                 .with_thread_names(true)
@@ -59,6 +76,7 @@ pub fn init_tracing(debug_level: u8) -> Result<()> {
         ),
         2.. => Box::new(
             fmt::layer()
+                .with_writer(writer())
                 .event_format(fmt::format().pretty())
                 .with_thread_ids(true)
                 .with_thread_names(true)
@@ -0,0 +1,216 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use color_eyre::{Result, eyre::eyre};
+use minijinja::value::{Value as JinjaValue, ValueKind};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constants::{ANSWERS_FILE, PROFILES_VAR},
+    db::TemplateResult,
+    template::context::without_boil_namespace,
+};
+
+const ANSWERS_HEADER: &str = "\
+# Written by `boil new`. `boil upgrade` uses it to update this project when its template changes.
+# Commit it, and don't edit [template] by hand.
+";
+
+/// What a project was generated from: written to `.boilermaker-answers.toml` in the project dir.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Answers {
+    pub template: AnswersTemplate,
+    /// The final context the project was rendered with, minus the built-in `boil` vars,
+    /// `profiles` and nulls (see `answers_context`).
+    pub context: JinjaValue,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnswersTemplate {
+    pub name: String,
+    pub lang: String,
+    pub repo: String,
+    pub branch: Option<String>,
+    pub subdir: Option<String>,
+    /// The template repo's commit. Missing for templates installed before commits were recorded.
+    pub commit: Option<String>,
+    pub boilermaker_version: String,
+    /// Every language rendered, with its subdir, when the project was generated from more than
+    /// one. Empty when `lang` was rendered into the project root.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub langs: Vec<ProjectLang>,
+}
+
+/// A language of a multi-language project and the project subdir it was rendered into.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectLang {
+    pub lang: String,
+    pub dir: String,
+}
+
+impl Answers {
+    pub fn new(t: &TemplateResult, langs: &[ProjectLang], ctx: &JinjaValue) -> Self {
+        Answers {
+            template: AnswersTemplate {
+                name: t.name.clone(),
+                lang: t.lang.clone(),
+                repo: t.repo.clone(),
+                branch: t.branch.clone(),
+                subdir: t.subdir.clone(),
+                commit: t.commit_sha.clone(),
+                boilermaker_version: env!("CARGO_PKG_VERSION").to_owned(),
+                langs: langs.to_vec(),
+            },
+            context: answers_context(ctx),
+        }
+    }
+
+    /// The languages to render and the subdir of each: `None` for a single-language project,
+    /// which is rendered into the project root.
+    pub fn lang_dirs(&self) -> Vec<(String, Option<String>)> {
+        if self.template.langs.is_empty() {
+            return vec![(self.template.lang.clone(), None)];
+        }
+        self.template
+            .langs
+            .iter()
+            .map(|l| (l.lang.clone(), Some(l.dir.clone())))
+            .collect()
+    }
+}
+
+/// The vars worth keeping for `boil upgrade`: `ctx` without the built-in `boil` vars and without
+/// `profiles` (the picked one is already merged in). Nulls, e.g. from a JSON vars file, are left
+/// out because TOML can't represent them.
+fn answers_context(ctx: &JinjaValue) -> JinjaValue {
+    let ctx = without_boil_namespace(ctx);
+    if ctx.kind() != ValueKind::Map {
+        return ctx;
+    }
+    let Ok(keys) = ctx.try_iter() else {
+        return ctx;
+    };
+    let vars = keys
+        .filter(|key| key.as_str() != Some(PROFILES_VAR))
+        .filter_map(|key| {
+            let value = without_nulls(ctx.get_item(&key).ok()?)?;
+            Some((key, value))
+        })
+        .collect::<BTreeMap<_, _>>();
+    JinjaValue::from_serialize(vars)
+}
+
+/// `v` with its null and undefined values removed, or `None` if it's null itself.
+fn without_nulls(v: JinjaValue) -> Option<JinjaValue> {
+    match v.kind() {
+        ValueKind::None | ValueKind::Undefined => None,
+        ValueKind::Map => {
+            let entries = v
+                .try_iter()
+                .into_iter()
+                .flatten()
+                .filter_map(|key| {
+                    let value = without_nulls(v.get_item(&key).ok()?)?;
+                    Some((key, value))
+                })
+                .collect::<BTreeMap<_, _>>();
+            Some(JinjaValue::from_serialize(entries))
+        }
+        ValueKind::Seq => Some(JinjaValue::from(
+            v.try_iter()
+                .into_iter()
+                .flatten()
+                .filter_map(without_nulls)
+                .collect::<Vec<_>>(),
+        )),
+        _ => Some(v),
+    }
+}
+
+#[tracing::instrument]
+pub fn write_answers(project_dir: &Path, answers: &Answers) -> Result<()> {
+    let text = toml::to_string(answers)
+        .map_err(|e| eyre!("💥 Failed to serialize {ANSWERS_FILE}: {e}"))?;
+    let path = project_dir.join(ANSWERS_FILE);
+    fs::write(&path, format!("{ANSWERS_HEADER}\n{text}"))
+        .map_err(|e| eyre!("💥 Failed to write {}: {e}", path.display()))
+}
+
+#[tracing::instrument]
+pub fn read_answers(project_dir: &Path) -> Result<Answers> {
+    let path = project_dir.join(ANSWERS_FILE);
+    if !path.is_file() {
+        return Err(eyre!(
+            "💥 Cannot find {}. Only projects created with `boil new` can be upgraded.",
+            path.display()
+        ));
+    }
+    let text = fs::read_to_string(&path)?;
+    toml::from_str(&text).map_err(|e| eyre!("💥 Invalid {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use minijinja::context;
+
+    use super::*;
+
+    fn template() -> TemplateResult {
+        TemplateResult {
+            id: 1,
+            name: "tmpl".to_owned(),
+            lang: "rust".to_owned(),
+            template_dir: "/tmp/tmpl".to_owned(),
+            repo: "https://example.com/tmpl".to_owned(),
+            branch: None,
+            subdir: None,
+            sha256_hash: None,
+            created_at: None,
+            updated_at: None,
+            commit_sha: Some("abc123".to_owned()),
+        }
+    }
+
+    #[test]
+    fn leaves_out_boil_profiles_and_nulls() {
+        let ctx = context!(
+            name => "api",
+            license => JinjaValue::from(()),
+            db => context!(host => "localhost", password => JinjaValue::from(())),
+            tags => vec![JinjaValue::from("a"), JinjaValue::from(()), JinjaValue::from("b")],
+            profiles => context!(prod => context!(name => "prod-api")),
+            boil => context!(year => 2026),
+        );
+        let answers = Answers::new(&template(), &[], &ctx);
+
+        let text = toml::to_string(&answers).unwrap();
+        let read: Answers = toml::from_str(&text).unwrap();
+        let context = read.context;
+        assert_eq!(context.get_attr("name").unwrap().as_str(), Some("api"));
+        assert!(context.get_attr("license").unwrap().is_undefined());
+        let db = context.get_attr("db").unwrap();
+        assert_eq!(db.get_attr("host").unwrap().as_str(), Some("localhost"));
+        assert!(db.get_attr("password").unwrap().is_undefined());
+        assert_eq!(
+            context.get_attr("tags").unwrap().to_string(),
+            r#"["a", "b"]"#
+        );
+        assert!(context.get_attr("profiles").unwrap().is_undefined());
+        assert!(context.get_attr("boil").unwrap().is_undefined());
+    }
+
+    #[test]
+    fn writes_and_reads_back() {
+        let dir = std::env::temp_dir().join(format!("boil-answers-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let ctx = context!(name => "api", optional => JinjaValue::from(()));
+        write_answers(&dir, &Answers::new(&template(), &[], &ctx)).unwrap();
+
+        let answers = read_answers(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(answers.template.commit.as_deref(), Some("abc123"));
+        assert_eq!(
+            answers.context.get_attr("name").unwrap().as_str(),
+            Some("api")
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use clap::ValueEnum;
+use color_eyre::Result;
+use tabled::Tabled;
+use tracing::warn;
+
+use crate::{
+    template::plan::{PlanAction, PlanEntry, diff_buffers},
+    util::{
+        file::{copy_file, read_text_file, side_file_path},
+        io::prompt_line,
+    },
+};
+
+/// What to do with a rendered file that already exists in the project dir with other content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ConflictPolicy {
+    /// Keep the existing file.
+    Skip,
+    /// Replace the existing file.
+    Overwrite,
+    /// Ask for every conflict.
+    Prompt,
+    /// Keep the existing file and write the changes as a diff to `<file>.rej`.
+    Rej,
+    /// Keep the existing file and write the rendered one to `<file>.new`.
+    New,
+    /// Add the rendered content to the end of the existing file.
+    Append,
+}
+
+impl fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self
+            .to_possible_value()
+            .map(|v| v.get_name().to_owned())
+            .unwrap_or_default();
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Clone, Tabled)]
+pub struct ApplyEntry {
+    #[tabled(rename = "Result")]
+    pub result: String,
+    #[tabled(rename = "Path")]
+    pub path: String,
+}
+
+/// Write the rendered project in `work_dir` into the existing `project_dir`, file by file.
+///
+/// New files are created and unchanged ones left alone. Files that differ are handled by `policy`;
+/// `Prompt` falls back to `Skip` when we can't ask (`interactive` is false). Nothing in
+/// `project_dir` is ever deleted.
+#[tracing::instrument(skip(plan))]
+pub fn apply_project(
+    work_dir: &Path,
+    project_dir: &Path,
+    plan: &[PlanEntry],
+    policy: ConflictPolicy,
+    interactive: bool,
+) -> Result<Vec<ApplyEntry>> {
+    let mut report: Vec<ApplyEntry> = Vec::new();
+    let mut policy = policy;
+
+    for entry in plan {
+        let src = work_dir.join(&entry.path);
+        let dest = project_dir.join(&entry.path);
+
+        let result = match entry.action {
+            PlanAction::Delete => continue,
+            PlanAction::Unchanged => "unchanged".to_owned(),
+            PlanAction::Create => {
+                copy_file(&src, &dest)?;
+                "created".to_owned()
+            }
+            PlanAction::Overwrite => {
+                let resolved = match policy {
+                    ConflictPolicy::Prompt if interactive => {
+                        let (choice, for_all) = prompt_conflict(&entry.path, &src, &dest)?;
+                        if for_all {
+                            policy = choice;
+                        }
+                        choice
+                    }
+                    ConflictPolicy::Prompt => {
+                        warn!(
+                            "Skipping conflicting file: {}. Pass --conflict to choose what to do.",
+                            entry.path
+                        );
+                        ConflictPolicy::Skip
+                    }
+                    p => p,
+                };
+                resolve_conflict(&entry.path, &src, &dest, resolved)?
+            }
+        };
+
+        report.push(ApplyEntry {
+            result,
+            path: entry.path.clone(),
+        });
+    }
+
+    Ok(report)
+}
+
+/// Handle one conflicting file, returning what was done.
+fn resolve_conflict(path: &str, src: &Path, dest: &Path, policy: ConflictPolicy) -> Result<String> {
+    match policy {
+        ConflictPolicy::Skip | ConflictPolicy::Prompt => Ok("skipped (conflict)".to_owned()),
+        ConflictPolicy::Overwrite => {
+            copy_file(src, dest)?;
+            Ok("overwritten".to_owned())
+        }
+        ConflictPolicy::New => {
+            let side = side_file_path(dest, "new");
+            copy_file(src, &side)?;
+            Ok(format!("kept, wrote {path}.new"))
+        }
+        ConflictPolicy::Rej => {
+            let diff = diff_buffers(path, &fs::read(dest)?, &fs::read(src)?)?;
+            fs::write(side_file_path(dest, "rej"), diff)?;
+            Ok(format!("kept, wrote {path}.rej"))
+        }
+        ConflictPolicy::Append => {
+            let (Some(existing), Some(rendered)) = (read_text_file(dest)?, read_text_file(src)?)
+            else {
+                warn!("Can't append to binary file {path}. Writing {path}.new instead.");
+                return resolve_conflict(path, src, dest, ConflictPolicy::New);
+            };
+            let mut file = OpenOptions::new().append(true).open(dest)?;
+            if !existing.is_empty() && !existing.ends_with('\n') {
+                file.write_all(b"\n")?;
+            }
+            file.write_all(rendered.as_bytes())?;
+            Ok("appended".to_owned())
+        }
+    }
+}
+
+/// Ask what to do with the conflicting file at `path`. Upper-case answers apply to every
+/// remaining conflict. If stdin is closed, it and every remaining conflict are skipped.
+fn prompt_conflict(path: &str, src: &Path, dest: &Path) -> Result<(ConflictPolicy, bool)> {
+    println!("{path} already exists and differs from the template.");
+    loop {
+        let Some(answer) = prompt_line(
+            "[s]kip, [o]verwrite, [a]ppend, write .[n]ew, write .[r]ej, show [d]iff (upper-case = all remaining): ",
+        )?
+        else {
+            warn!("Input closed, so {path} and any remaining conflicts are skipped.");
+            return Ok((ConflictPolicy::Skip, true));
+        };
+        let for_all = answer.len() == 1 && answer.chars().all(|c| c.is_ascii_uppercase());
+        let choice = match answer.to_lowercase().as_str() {
+            "s" | "skip" => ConflictPolicy::Skip,
+            "o" | "overwrite" => ConflictPolicy::Overwrite,
+            "a" | "append" => ConflictPolicy::Append,
+            "n" | "new" => ConflictPolicy::New,
+            "r" | "rej" => ConflictPolicy::Rej,
+            "d" | "diff" => {
+                println!("{}", diff_buffers(path, &fs::read(dest)?, &fs::read(src)?)?);
+                continue;
+            }
+            _ => {
+                println!("Please answer s, o, a, n, r or d.");
+                continue;
+            }
+        };
+        return Ok((choice, for_all));
+    }
+}
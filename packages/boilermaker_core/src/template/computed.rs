@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use color_eyre::{Result, eyre::eyre};
+use lazy_static::lazy_static;
+use minijinja::{
+    Environment as JinjaEnv, UndefinedBehavior,
+    value::{Value as JinjaValue, merge_maps},
+};
+use regex::Regex;
+
+use crate::{
+    config::TemplateConfigProject,
+    template::{
+        context::{leaf_paths, nest_at_path, value_at_path},
+        filters::register_builtins,
+        merge::deep_merge,
+    },
+};
+
+lazy_static! {
+    static ref COMPUTED_ENV: JinjaEnv<'static> = {
+        let mut jinja = JinjaEnv::new();
+        register_builtins(&mut jinja);
+        jinja.set_undefined_behavior(UndefinedBehavior::Strict);
+        jinja
+    };
+    /// A value that's nothing but one `{{ expression }}`.
+    static ref SINGLE_EXPR: Regex = Regex::new(r"^\s*\{\{(?<expr>(?s:.)*?)\}\}\s*$").unwrap();
+}
+
+/// Evaluate the template's `[computed]` vars and add them to `ctx`.
+///
+/// Each value is rendered against `ctx`, plus the template's `[project]` metadata as `project`
+/// (unless `ctx` has its own `project`). A value that's a single `{{ expression }}` keeps the
+/// expression's type, e.g. a number or a list. Computed vars can use each other, so they're
+/// evaluated in dependency order and a cycle is an error.
+///
+/// Paths in `keep` were set by the user (`--var`, vars files) and aren't recomputed.
+#[tracing::instrument]
+pub fn apply_computed_vars(
+    ctx: JinjaValue,
+    computed: &JinjaValue,
+    project: &TemplateConfigProject,
+    keep: &HashSet<String>,
+) -> Result<JinjaValue> {
+    let paths = leaf_paths(computed)
+        .into_iter()
+        .filter(|path| !keep.iter().any(|k| paths_overlap(k, path)))
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        return Ok(ctx);
+    }
+
+    let mut deps: HashMap<&str, Vec<&str>> = HashMap::new();
+    for path in &paths {
+        let refs = match value_at_path(computed, path).as_str() {
+            Some(src) => COMPUTED_ENV
+                .template_from_str(src)
+                .map_err(|e| eyre!("💥 Invalid computed var {path}: {e}"))?
+                .undeclared_variables(true),
+            None => HashSet::new(),
+        };
+        let path_deps = paths
+            .iter()
+            .filter(|other| refs.iter().any(|r| paths_overlap(r, other)))
+            .map(String::as_str)
+            .collect();
+        deps.insert(path.as_str(), path_deps);
+    }
+
+    let project = JinjaValue::from_serialize(BTreeMap::from([(
+        "project",
+        JinjaValue::from_serialize(project),
+    )]));
+    let mut ctx = ctx;
+    for path in dependency_order(&paths, &deps)? {
+        let value = value_at_path(computed, path);
+        let value = match value.as_str() {
+            Some(src) => eval_computed(src, &merge_maps([project.clone(), ctx.clone()]))
+                .map_err(|e| eyre!("💥 Failed to compute {path}: {e}"))?,
+            None => value,
+        };
+        ctx = deep_merge(vec![ctx, nest_at_path(path, value)?], None);
+    }
+
+    Ok(ctx)
+}
+
+fn eval_computed(src: &str, ctx: &JinjaValue) -> Result<JinjaValue, minijinja::Error> {
+    match SINGLE_EXPR.captures(src) {
+        Some(caps) if !caps["expr"].contains("{{") => {
+            COMPUTED_ENV.compile_expression(&caps["expr"])?.eval(ctx)
+        }
+        _ => COMPUTED_ENV.render_str(src, ctx).map(JinjaValue::from),
+    }
+}
+
+/// `paths` ordered so each comes after the ones it depends on. Reports the first cycle found.
+fn dependency_order<'a>(
+    paths: &'a [String],
+    deps: &HashMap<&'a str, Vec<&'a str>>,
+) -> Result<Vec<&'a str>> {
+    fn visit<'a>(
+        path: &'a str,
+        deps: &HashMap<&'a str, Vec<&'a str>>,
+        done: &mut HashSet<&'a str>,
+        stack: &mut Vec<&'a str>,
+        order: &mut Vec<&'a str>,
+    ) -> Result<()> {
+        if done.contains(path) {
+            return Ok(());
+        }
+        if let Some(start) = stack.iter().position(|p| *p == path) {
+            let cycle = [&stack[start..], &[path]].concat();
+            return Err(eyre!(
+                "💥 Computed vars depend on each other in a loop: {}",
+                cycle.join(" → ")
+            ));
+        }
+
+        stack.push(path);
+        for dep in &deps[path] {
+            visit(dep, deps, done, stack, order)?;
+        }
+        stack.pop();
+
+        done.insert(path);
+        order.push(path);
+        Ok(())
+    }
+
+    let mut done = HashSet::new();
+    let mut order = Vec::with_capacity(paths.len());
+    for path in paths {
+        visit(path, deps, &mut done, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+}
+
+/// Whether one dot-delimited path is the other or contains it, e.g. `db` and `db.port`.
+fn paths_overlap(a: &str, b: &str) -> bool {
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    long == short
+        || long
+            .strip_prefix(short)
+            .is_some_and(|rest| rest.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use minijinja::context;
+
+    use super::*;
+
+    fn project() -> TemplateConfigProject {
+        TemplateConfigProject {
+            name: "tmpl".to_owned(),
+            version: "1.2.0".to_owned(),
+            repository: "https://example.com/tmpl".to_owned(),
+            default_branch: None,
+            default_subdir: None,
+            default_lang: None,
+            description: None,
+            authors: None,
+            license: None,
+            keywords: None,
+            website: None,
+        }
+    }
+
+    fn apply(ctx: JinjaValue, computed: JinjaValue, keep: &[&str]) -> Result<JinjaValue> {
+        let keep = keep.iter().map(|k| k.to_string()).collect();
+        apply_computed_vars(ctx, &computed, &project(), &keep)
+    }
+
+    fn order<'a>(paths: &'a [String], deps: &[(&'a str, &[&'a str])]) -> Result<Vec<&'a str>> {
+        let deps = deps
+            .iter()
+            .map(|(path, on)| (*path, on.to_vec()))
+            .collect::<HashMap<_, _>>();
+        dependency_order(paths, &deps)
+    }
+
+    fn strings(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn orders_dependencies_first() {
+        let paths = strings(&["c", "b", "a"]);
+        let order = order(&paths, &[("c", &["b"]), ("b", &["a"]), ("a", &[])]).unwrap();
+        assert_eq!(order, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn keeps_independent_paths_in_order() {
+        let paths = strings(&["x", "y", "z"]);
+        let order = order(&paths, &[("x", &[]), ("y", &[]), ("z", &["x"])]).unwrap();
+        assert_eq!(order, vec!["x", "y", "z"]);
+    }
+
+    #[test]
+    fn reports_cycles() {
+        let paths = strings(&["a", "b", "c"]);
+        let err = order(&paths, &[("a", &["b"]), ("b", &["c"]), ("c", &["a"])]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "💥 Computed vars depend on each other in a loop: a → b → c → a"
+        );
+    }
+
+    #[test]
+    fn reports_self_references() {
+        let err = apply(context!(), context!(a => "{{ a }}"), &[]).unwrap_err();
+        assert!(err.to_string().contains("in a loop: a → a"), "{err}");
+    }
+
+    #[test]
+    fn evaluates_in_dependency_order() {
+        let ctx = apply(
+            context!(name => "api"),
+            context!(
+                url => "{{ host }}:{{ port }}",
+                host => "{{ name }}.local",
+                port => "{{ 8000 + 80 }}",
+            ),
+            &[],
+        )
+        .unwrap();
+        assert_eq!(
+            ctx.get_attr("url").unwrap().as_str(),
+            Some("api.local:8080")
+        );
+    }
+
+    #[test]
+    fn single_expressions_keep_their_type() {
+        let ctx = apply(
+            context!(names => vec!["a", "b"]),
+            context!(count => "{{ names|length }}", upper => "{{ names|map('upper')|list }}"),
+            &[],
+        )
+        .unwrap();
+        assert_eq!(ctx.get_attr("count").unwrap().as_i64(), Some(2));
+        assert_eq!(ctx.get_attr("upper").unwrap().to_string(), r#"["A", "B"]"#);
+    }
+
+    #[test]
+    fn nested_paths_depend_on_each_other() {
+        let ctx = apply(
+            context!(),
+            context!(db => context!(port => 5432, url => "localhost:{{ db.port }}")),
+            &[],
+        )
+        .unwrap();
+        let db = ctx.get_attr("db").unwrap();
+        assert_eq!(db.get_attr("port").unwrap().as_i64(), Some(5432));
+        assert_eq!(db.get_attr("url").unwrap().as_str(), Some("localhost:5432"));
+    }
+
+    #[test]
+    fn kept_paths_are_not_recomputed() {
+        let ctx = apply(
+            context!(slug => "custom"),
+            context!(slug => "{{ 'x' }}", path => "/srv/{{ slug }}"),
+            &["slug"],
+        )
+        .unwrap();
+        assert_eq!(ctx.get_attr("slug").unwrap().as_str(), Some("custom"));
+        assert_eq!(ctx.get_attr("path").unwrap().as_str(), Some("/srv/custom"));
+    }
+
+    #[test]
+    fn project_metadata_is_available_unless_shadowed() {
+        let computed = context!(tag => "{{ project.name }}-{{ project.version }}");
+        let ctx = apply(context!(), computed.clone(), &[]).unwrap();
+        assert_eq!(ctx.get_attr("tag").unwrap().as_str(), Some("tmpl-1.2.0"));
+
+        let ctx = apply(
+            context!(project => context!(name => "mine", version => "0.1")),
+            computed,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(ctx.get_attr("tag").unwrap().as_str(), Some("mine-0.1"));
+    }
+
+    #[test]
+    fn paths_overlap_on_dot_boundaries() {
+        assert!(paths_overlap("db", "db"));
+        assert!(paths_overlap("db", "db.port"));
+        assert!(paths_overlap("db.port", "db"));
+        assert!(!paths_overlap("db", "dbx"));
+        assert!(!paths_overlap("db.port", "db.host"));
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{Datelike, Local};
+use color_eyre::{Result, eyre::eyre};
+use minijinja::value::{Value as JinjaValue, ValueKind};
+use tracing::warn;
+
+use crate::{
+    config::TemplateConfigProject, constants::BOIL_NAMESPACE, template::merge::deep_merge,
+};
+
+/// Wrap `value` in nested maps following a dot-delimited `path`.
+///
+/// `nest_at_path("db.port", 5432)` gives `{ db = { port = 5432 } }`.
+pub fn nest_at_path(path: &str, value: JinjaValue) -> Result<JinjaValue> {
+    let segments = path.split('.').collect::<Vec<_>>();
+    if segments.iter().any(|s| s.trim().is_empty()) {
+        return Err(eyre!("💥 Invalid variable path: {path:?}"));
+    }
+
+    Ok(segments.into_iter().rev().fold(value, |inner, segment| {
+        JinjaValue::from_serialize(BTreeMap::from([(segment.trim().to_owned(), inner)]))
+    }))
+}
+
+/// Look up a dot-delimited `path` in `ctx`. Returns undefined if any segment is missing.
+pub fn value_at_path(ctx: &JinjaValue, path: &str) -> JinjaValue {
+    path.split('.').fold(ctx.clone(), |v, segment| {
+        v.get_attr(segment).unwrap_or_default()
+    })
+}
+
+/// Whether a variable reference like `config.db.host` resolves in `ctx`.
+///
+/// Walking stops at the first value that isn't a map, so attributes and methods of strings and
+/// lists (`name.upper`, `items.length`) count as defined.
+pub fn is_defined_path(ctx: &JinjaValue, path: &str) -> bool {
+    let mut v = ctx.clone();
+    for segment in path.split('.') {
+        if v.kind() != ValueKind::Map {
+            return true;
+        }
+        v = v.get_attr(segment).unwrap_or_default();
+        if v.is_undefined() {
+            return false;
+        }
+    }
+    true
+}
+
+/// Dot-delimited paths to every non-map value in `ctx`. Lists count as leaves.
+pub fn leaf_paths(ctx: &JinjaValue) -> Vec<String> {
+    let mut paths = Vec::new();
+    collect_leaf_paths(ctx, "", &mut paths);
+    paths
+}
+
+fn collect_leaf_paths(v: &JinjaValue, prefix: &str, paths: &mut Vec<String>) {
+    if v.kind() != ValueKind::Map {
+        if !prefix.is_empty() {
+            paths.push(prefix.to_owned());
+        }
+        return;
+    }
+
+    let Ok(keys) = v.try_iter() else {
+        return;
+    };
+    for key in keys {
+        let Some(name) = key.as_str() else {
+            continue;
+        };
+        let path = if prefix.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{prefix}.{name}")
+        };
+        collect_leaf_paths(&v.get_item(&key).unwrap_or_default(), &path, paths);
+    }
+}
+
+/// Add the built-in `boil` vars to `ctx`, for a project generated into `project_dir` from a
+/// template with `project` metadata. A `boil` var set by the template or the user is replaced.
+#[tracing::instrument]
+pub fn with_boil_namespace(
+    ctx: JinjaValue,
+    project_dir: &Path,
+    project: &TemplateConfigProject,
+) -> JinjaValue {
+    if ctx
+        .get_attr(BOIL_NAMESPACE)
+        .is_ok_and(|v| !v.is_undefined())
+    {
+        warn!(
+            "`{BOIL_NAMESPACE}` is reserved for built-in vars, so the `{BOIL_NAMESPACE}` var is ignored."
+        );
+    }
+    let mut vars = vars_without_boil(&ctx);
+    vars.insert(
+        JinjaValue::from(BOIL_NAMESPACE),
+        boil_namespace(project_dir, project),
+    );
+    JinjaValue::from_serialize(vars)
+}
+
+/// `ctx` without the built-in `boil` vars, which are worked out again on every render.
+pub fn without_boil_namespace(ctx: &JinjaValue) -> JinjaValue {
+    if ctx.kind() != ValueKind::Map {
+        return ctx.clone();
+    }
+    JinjaValue::from_serialize(vars_without_boil(ctx))
+}
+
+fn vars_without_boil(ctx: &JinjaValue) -> BTreeMap<JinjaValue, JinjaValue> {
+    let Ok(keys) = ctx.try_iter() else {
+        return BTreeMap::new();
+    };
+    keys.filter(|key| key.as_str() != Some(BOIL_NAMESPACE))
+        .map(|key| {
+            let value = ctx.get_item(&key).unwrap_or_default();
+            (key, value)
+        })
+        .collect()
+}
+
+/// The built-in vars. Values that can't be found (e.g. no git `user.email`) are left out, so
+/// templates can fall back with `default`.
+fn boil_namespace(project_dir: &Path, project: &TemplateConfigProject) -> JinjaValue {
+    let now = Local::now();
+    let mut vars = BTreeMap::from([
+        ("date", JinjaValue::from(now.format("%Y-%m-%d").to_string())),
+        ("year", JinjaValue::from(now.year())),
+        ("os", JinjaValue::from(env::consts::OS)),
+        ("version", JinjaValue::from(env!("CARGO_PKG_VERSION"))),
+        ("project", JinjaValue::from_serialize(project)),
+    ]);
+
+    if let Ok(user) = env::var("USER").or_else(|_| env::var("USERNAME")) {
+        vars.insert("user", JinjaValue::from(user));
+    }
+
+    let project_dir = fs::canonicalize(project_dir).unwrap_or_else(|_| PathBuf::from(project_dir));
+    if let Some(name) = project_dir.file_name() {
+        vars.insert("dir_name", JinjaValue::from(name.to_string_lossy()));
+    }
+
+    let mut git = BTreeMap::new();
+    if let Ok(config) = git2::Config::open_default() {
+        for (key, name) in [("user.name", "name"), ("user.email", "email")] {
+            if let Ok(value) = config.get_string(key) {
+                git.insert(name, JinjaValue::from(value));
+            }
+        }
+    }
+    vars.insert("git", JinjaValue::from_serialize(git));
+
+    JinjaValue::from_serialize(vars)
+}
+
+/// Load an extra vars file (`--vars-file`). The format is picked from the extension: `.toml`,
+/// `.json`, `.yaml`/`.yml`, or `.env` (also `.env.*` files, e.g. `.env.production`).
+#[tracing::instrument]
+pub fn load_vars_file(path: &Path) -> Result<JinjaValue> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| eyre!("💥 Failed to read vars file `{}`: {e}", path.display()))?;
+    let file_name = path
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let parsed = if file_name.starts_with(".env") || ext == "env" {
+        parse_env_vars(&text)
+    } else {
+        match ext.as_str() {
+            "toml" => toml::from_str::<toml::Table>(&text)
+                .map(JinjaValue::from_serialize)
+                .map_err(|e| eyre!(e)),
+            "json" => serde_json::from_str::<serde_json::Value>(&text)
+                .map(JinjaValue::from_serialize)
+                .map_err(|e| eyre!(e)),
+            "yaml" | "yml" => serde_yaml::from_str::<serde_yaml::Value>(&text)
+                .map(JinjaValue::from_serialize)
+                .map_err(|e| eyre!(e)),
+            _ => {
+                return Err(eyre!(
+                    "💥 Unsupported vars file format: `{}`. Use .toml, .json, .yaml or .env.",
+                    path.display()
+                ));
+            }
+        }
+    };
+
+    let vars =
+        parsed.map_err(|e| eyre!("💥 Failed to parse vars file `{}`: {e}", path.display()))?;
+    if vars.kind() != ValueKind::Map {
+        return Err(eyre!(
+            "💥 Vars file `{}` must contain a table/object at the top level.",
+            path.display()
+        ));
+    }
+
+    Ok(vars)
+}
+
+/// Parse `KEY=VALUE` lines. Blank lines, `#` comments and a leading `export` are ignored,
+/// surrounding quotes are stripped, and dotted keys become nested vars.
+fn parse_env_vars(text: &str) -> Result<JinjaValue> {
+    let mut contexts = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(eyre!("line {}: expected KEY=VALUE, got {line:?}", i + 1));
+        };
+
+        let value = value.trim();
+        let value = ['"', '\'']
+            .iter()
+            .find_map(|q| value.strip_prefix(*q).and_then(|v| v.strip_suffix(*q)))
+            .unwrap_or(value);
+
+        contexts.push(nest_at_path(key.trim(), JinjaValue::from(value))?);
+    }
+
+    Ok(deep_merge(contexts, None))
+}
@@ -0,0 +1,166 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use chrono::Local;
+use heck::{ToKebabCase, ToLowerCamelCase, ToShoutySnakeCase, ToSnakeCase, ToUpperCamelCase};
+use minijinja::{Environment as JinjaEnv, Error, ErrorKind, value::Value as JinjaValue};
+
+use crate::util::{
+    crypto::sha256_hash_string,
+    math::rand_alphanumeric,
+    string::{pluralize as pluralize_str, slugify},
+};
+
+const DEFAULT_NOW_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%:z";
+const DEFAULT_TODAY_FORMAT: &str = "%Y-%m-%d";
+/// Longest string `random_string` makes, so a typo'd length can't exhaust memory.
+const MAX_RANDOM_STRING_LEN: usize = 4096;
+
+/// Common SPDX license identifiers and their full names.
+const SPDX_LICENSES: &[(&str, &str)] = &[
+    ("0BSD", "BSD Zero Clause License"),
+    (
+        "AGPL-3.0-only",
+        "GNU Affero General Public License v3.0 only",
+    ),
+    (
+        "AGPL-3.0-or-later",
+        "GNU Affero General Public License v3.0 or later",
+    ),
+    ("Apache-2.0", "Apache License 2.0"),
+    ("Artistic-2.0", "Artistic License 2.0"),
+    ("BSD-2-Clause", "BSD 2-Clause \"Simplified\" License"),
+    (
+        "BSD-3-Clause",
+        "BSD 3-Clause \"New\" or \"Revised\" License",
+    ),
+    ("BSL-1.0", "Boost Software License 1.0"),
+    (
+        "CC-BY-4.0",
+        "Creative Commons Attribution 4.0 International",
+    ),
+    (
+        "CC-BY-SA-4.0",
+        "Creative Commons Attribution Share Alike 4.0 International",
+    ),
+    ("CC0-1.0", "Creative Commons Zero v1.0 Universal"),
+    ("EPL-2.0", "Eclipse Public License 2.0"),
+    ("EUPL-1.2", "European Union Public License 1.2"),
+    ("GPL-2.0-only", "GNU General Public License v2.0 only"),
+    (
+        "GPL-2.0-or-later",
+        "GNU General Public License v2.0 or later",
+    ),
+    ("GPL-3.0-only", "GNU General Public License v3.0 only"),
+    (
+        "GPL-3.0-or-later",
+        "GNU General Public License v3.0 or later",
+    ),
+    ("ISC", "ISC License"),
+    (
+        "LGPL-2.1-only",
+        "GNU Lesser General Public License v2.1 only",
+    ),
+    (
+        "LGPL-2.1-or-later",
+        "GNU Lesser General Public License v2.1 or later",
+    ),
+    (
+        "LGPL-3.0-only",
+        "GNU Lesser General Public License v3.0 only",
+    ),
+    (
+        "LGPL-3.0-or-later",
+        "GNU Lesser General Public License v3.0 or later",
+    ),
+    ("MIT", "MIT License"),
+    ("MIT-0", "MIT No Attribution"),
+    ("MPL-2.0", "Mozilla Public License 2.0"),
+    ("OSL-3.0", "Open Software License 3.0"),
+    ("Unicode-3.0", "Unicode License v3"),
+    ("Unlicense", "The Unlicense"),
+    ("WTFPL", "Do What The F*ck You Want To Public License"),
+    ("Zlib", "zlib License"),
+];
+
+/// Register Boilermaker's filters and functions on `jinja`.
+///
+/// Used for every environment that renders template content or file paths, so both see exactly
+/// the same library.
+pub fn register_builtins(jinja: &mut JinjaEnv) {
+    jinja.add_filter("snake_case", |s: &str| s.to_snake_case());
+    jinja.add_filter("kebab_case", |s: &str| s.to_kebab_case());
+    jinja.add_filter("camel_case", |s: &str| s.to_lower_camel_case());
+    jinja.add_filter("pascal_case", |s: &str| s.to_upper_camel_case());
+    jinja.add_filter("screaming_snake_case", |s: &str| s.to_shouty_snake_case());
+    jinja.add_filter("pluralize", pluralize);
+    jinja.add_filter("slugify", |s: &str| slugify(s));
+    jinja.add_filter("sha256", |s: &str| sha256_hash_string(s));
+    jinja.add_filter("license_name", |id: &str| {
+        license(id).and_then(|l| l.get_attr("name"))
+    });
+    jinja.add_filter("license_url", |id: &str| {
+        license(id).and_then(|l| l.get_attr("url"))
+    });
+
+    jinja.add_function("uuid", || uuid::Uuid::new_v4().to_string());
+    jinja.add_function("now", |format: Option<&str>| {
+        format_now(format.unwrap_or(DEFAULT_NOW_FORMAT))
+    });
+    jinja.add_function("today", |format: Option<&str>| {
+        format_now(format.unwrap_or(DEFAULT_TODAY_FORMAT))
+    });
+    jinja.add_function("random_string", random_string);
+    jinja.add_function("license", license);
+}
+
+/// The local time formatted with `strftime`-style `format`, e.g. `now("%H:%M")`.
+fn format_now(format: &str) -> Result<String, Error> {
+    let mut out = String::new();
+    write!(out, "{}", Local::now().format(format)).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("invalid date format: {format:?}"),
+        )
+    })?;
+    Ok(out)
+}
+
+/// `n` random letters and digits, up to `MAX_RANDOM_STRING_LEN`.
+fn random_string(len: usize) -> Result<String, Error> {
+    if len > MAX_RANDOM_STRING_LEN {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("random_string length {len} is over the maximum of {MAX_RANDOM_STRING_LEN}"),
+        ));
+    }
+    Ok(rand_alphanumeric(len))
+}
+
+/// `{{ "category"|pluralize }}` → `categories`. With a count, the word is only pluralized when
+/// the count isn't 1: `{{ "file"|pluralize(n) }}`.
+fn pluralize(s: &str, count: Option<i64>) -> String {
+    if count == Some(1) {
+        s.to_owned()
+    } else {
+        pluralize_str(s)
+    }
+}
+
+/// Look up an SPDX license by id (case-insensitive), e.g. `license("apache-2.0").name`.
+fn license(id: &str) -> Result<JinjaValue, Error> {
+    let (id, name) = SPDX_LICENSES
+        .iter()
+        .find(|(spdx_id, _)| spdx_id.eq_ignore_ascii_case(id.trim()))
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidOperation,
+                format!("unknown SPDX license id: {id:?}"),
+            )
+        })?;
+
+    Ok(JinjaValue::from_serialize(BTreeMap::from([
+        ("id", id.to_string()),
+        ("name", name.to_string()),
+        ("url", format!("https://spdx.org/licenses/{id}.html")),
+    ])))
+}
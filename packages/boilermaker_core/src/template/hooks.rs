@@ -0,0 +1,185 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use color_eyre::{Result, eyre::eyre};
+use minijinja::value::Value as JinjaValue;
+use tracing::{info, warn};
+
+use crate::{
+    constants::{BOIL_NAMESPACE, PROFILES_VAR},
+    template::context::value_at_path,
+    util::io::prompt_line,
+};
+
+/// Prefix for template variables passed to hooks, e.g. `app_name` → `BOIL_VAR_APP_NAME`.
+const VAR_ENV_PREFIX: &str = "BOIL_VAR_";
+/// Built-in `boil` vars passed to hooks, and their environment variable names.
+const BOIL_ENV_VARS: &[(&str, &str)] = &[
+    ("date", "BOIL_DATE"),
+    ("year", "BOIL_YEAR"),
+    ("user", "BOIL_USER"),
+    ("git.name", "BOIL_GIT_NAME"),
+    ("git.email", "BOIL_GIT_EMAIL"),
+    ("os", "BOIL_OS"),
+    ("version", "BOIL_VERSION"),
+    ("project.name", "BOIL_TEMPLATE_NAME"),
+    ("project.version", "BOIL_TEMPLATE_VERSION"),
+];
+
+/// Show the template's `stage` hooks and ask before running any of them.
+///
+/// Returns `true` right away when `allow` is set (`--allow-hooks`). When we can't ask
+/// (`interactive` is false), hooks are skipped.
+#[tracing::instrument]
+pub fn confirm_hooks(
+    stage: &str,
+    hooks: &[String],
+    allow: bool,
+    interactive: bool,
+) -> Result<bool> {
+    if hooks.is_empty() || allow {
+        return Ok(true);
+    }
+
+    if !interactive {
+        warn!(
+            "Skipping {stage} hooks. Pass --allow-hooks to run them:\n  $ {}",
+            hooks.join("\n  $ ")
+        );
+        return Ok(false);
+    }
+
+    println!("This template wants to run the following {stage} hooks:");
+    for hook in hooks {
+        println!("  $ {hook}");
+    }
+
+    let answer = prompt_line("Run them? [y/N]: ")?.unwrap_or_default();
+    let confirmed = matches!(answer.to_lowercase().as_str(), "y" | "yes");
+    if !confirmed {
+        info!("Skipping {stage} hooks.");
+    }
+    Ok(confirmed)
+}
+
+/// Run `hooks` one by one through the shell in `dir`, stopping at the first failure.
+///
+/// Every template variable is passed in an environment variable (see `hook_env`), along with
+/// `BOIL_PROJECT_DIR`, `BOIL_PROJECT_NAME` and the whole context as JSON in `BOIL_CONTEXT`.
+#[tracing::instrument]
+pub fn run_hooks(stage: &str, hooks: &[String], dir: &Path, ctx: &JinjaValue) -> Result<()> {
+    let env = hook_env(dir, ctx)?;
+
+    for (i, hook) in hooks.iter().enumerate() {
+        info!("Running {stage} hook: {hook}");
+
+        let status = shell_command(hook)
+            .current_dir(dir)
+            .envs(env.iter().map(|(k, v)| (k, v)))
+            .status()
+            .map_err(|e| eyre!("💥 Failed to start {stage} hook `{hook}`: {e}"))?;
+
+        if !status.success() {
+            let skipped = &hooks[i + 1..];
+            let skipped = if skipped.is_empty() {
+                String::new()
+            } else {
+                format!("\nSkipped hooks:\n  $ {}", skipped.join("\n  $ "))
+            };
+            return Err(eyre!(
+                "💥 {stage} hook failed ({status}): `{hook}`{skipped}"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Run every check hook in `dir` (the installed template), returning a line for each one that
+/// failed. `BOIL_TEMPLATE_DIR` points at `dir`.
+#[tracing::instrument]
+pub fn run_check_hooks(stage: &str, hooks: &[String], dir: &Path) -> Vec<String> {
+    let mut failures = Vec::new();
+
+    for hook in hooks {
+        info!("Running {stage} hook: {hook}");
+
+        let status = shell_command(hook)
+            .current_dir(dir)
+            .env("BOIL_TEMPLATE_DIR", dir)
+            .stdout(Stdio::from(io::stderr()))
+            .status();
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => failures.push(format!("{stage} check failed ({status}): `{hook}`")),
+            Err(e) => failures.push(format!("{stage} check couldn't start: `{hook}`: {e}")),
+        }
+    }
+
+    failures
+}
+
+/// Environment variables for hooks: each top-level var, the built-in `boil` vars under their own
+/// names and the context as JSON, all without `profiles`. Non-string values are passed as JSON.
+#[tracing::instrument]
+pub fn hook_env(dir: &Path, ctx: &JinjaValue) -> Result<Vec<(String, String)>> {
+    let mut vars = BTreeMap::new();
+    if let Ok(keys) = ctx.try_iter() {
+        for key in keys {
+            // Profiles hold values for the profiles that weren't picked, so they're never passed.
+            if let Some(name) = key.as_str().filter(|name| *name != PROFILES_VAR) {
+                vars.insert(name.to_owned(), ctx.get_item(&key).unwrap_or_default());
+            }
+        }
+    }
+
+    let mut env = vec![
+        ("BOIL_PROJECT_DIR".to_owned(), dir.display().to_string()),
+        (
+            "BOIL_PROJECT_NAME".to_owned(),
+            dir.file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        ),
+        ("BOIL_CONTEXT".to_owned(), serde_json::to_string(&vars)?),
+    ];
+
+    if let Some(boil) = vars.remove(BOIL_NAMESPACE) {
+        for (path, name) in BOIL_ENV_VARS {
+            let value = value_at_path(&boil, path);
+            if !value.is_undefined() && !value.is_none() {
+                env.push((name.to_string(), env_value(&value)?));
+            }
+        }
+    }
+
+    for (name, value) in &vars {
+        let name = name.replace('-', "_").to_uppercase();
+        env.push((format!("{VAR_ENV_PREFIX}{name}"), env_value(value)?));
+    }
+
+    Ok(env)
+}
+
+fn env_value(value: &JinjaValue) -> Result<String> {
+    Ok(match value.as_str() {
+        Some(s) => s.to_owned(),
+        None => serde_json::to_string(value)?,
+    })
+}
+
+fn shell_command(cmd: &str) -> Command {
+    if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", cmd]);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.args(["-c", cmd]);
+        command
+    }
+}
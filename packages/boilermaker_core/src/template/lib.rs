@@ -1,10 +1,21 @@
-use std::{env, fs, path::PathBuf};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
 use auth_git2::GitAuthenticator;
 use color_eyre::{Result, eyre::eyre};
 use fs_extra::dir::{CopyOptions, copy};
-use git2::{Config, FetchOptions, RemoteCallbacks, Repository, build::RepoBuilder};
-use minijinja::{Environment as JinjaEnv, value::Value as JinjaValue};
+use git2::{
+    Config, FetchOptions, Oid, RemoteCallbacks, Repository,
+    build::{CheckoutBuilder, RepoBuilder},
+};
+use lazy_static::lazy_static;
+use minijinja::{
+    Environment as JinjaEnv, UndefinedBehavior, syntax::SyntaxConfig, value::Value as JinjaValue,
+};
+use tabled::Tabled;
 use tracing::info;
 use walkdir::WalkDir;
 
@@ -13,11 +24,21 @@ pub use crate::config::{
 };
 use crate::{
     config::{TemplateConfig, get_template_base_dir},
-    constants::TEMPLATE_FILEPATH_VAR_PATTERN as FILEPATH_VARS,
+    constants::COMMON_DIR,
     db::HashableTemplateValues,
+    template::{
+        RenderRules,
+        filters::register_builtins,
+        paths::{PathScopes, expand_path_loops, interpolate_template_filepaths},
+        render::RenderEnv,
+        rules::is_at_or_under,
+        static_analysis::find_undefined_vars,
+    },
     util::{
         crypto::sha256_hash_string,
-        file::{clean_dir, list_dir, move_file},
+        file::{clean_dir, list_dir, read_text_file},
+        output::format_table,
+        pool::{par_map, pool_size},
     },
 };
 
@@ -82,6 +103,42 @@ pub async fn clone_repo(ctx: &CloneContext) -> Result<Repository> {
     Ok(repo?)
 }
 
+/// The commit `repo`'s HEAD points at, if any.
+#[tracing::instrument(skip(repo))]
+pub fn head_commit_sha(repo: &Repository) -> Option<String> {
+    repo.head()
+        .and_then(|head| head.peel_to_commit())
+        .map(|commit| commit.id().to_string())
+        .ok()
+}
+
+/// Check out `commit` in `repo`, fetching it from `origin` first when a shallow clone doesn't
+/// have it.
+#[tracing::instrument(skip(repo))]
+pub fn checkout_commit(repo: &Repository, commit: &str) -> Result<()> {
+    let oid = Oid::from_str(commit).map_err(|e| eyre!("💥 Invalid commit: {commit}: {e}"))?;
+
+    if repo.find_commit(oid).is_err() {
+        let auth = GitAuthenticator::default();
+        let git_config = Config::open_default()?;
+        let mut fetch_opts = FetchOptions::new();
+        let mut remote_callbacks = RemoteCallbacks::new();
+        remote_callbacks.credentials(auth.credentials(&git_config));
+        fetch_opts.remote_callbacks(remote_callbacks);
+        repo.find_remote("origin")?
+            .fetch(&[commit], Some(&mut fetch_opts), None)
+            .map_err(|e| eyre!("💥 Failed to fetch commit {commit}: {e}"))?;
+    }
+
+    let found = repo
+        .find_commit(oid)
+        .map_err(|e| eyre!("💥 Cannot find commit {commit}: {e}"))?;
+    repo.checkout_tree(found.as_object(), Some(CheckoutBuilder::new().force()))?;
+    repo.set_head_detached(oid)?;
+
+    Ok(())
+}
+
 #[tracing::instrument]
 pub async fn open_repo(ctx: &CloneContext) -> Result<Repository> {
     let path = PathBuf::from(&ctx.url);
@@ -142,8 +199,13 @@ pub fn create_template_dir(name: &str) -> Result<PathBuf> {
     Ok(template_dir)
 }
 
+/// Copy the template in `src_path` to `dest_path`, leaving out files excluded by `rules`.
 #[tracing::instrument]
-pub async fn install_template(src_path: &PathBuf, dest_path: &PathBuf) -> Result<()> {
+pub async fn install_template(
+    src_path: &PathBuf,
+    dest_path: &PathBuf,
+    rules: &RenderRules,
+) -> Result<()> {
     if dest_path.exists() {
         return Err(eyre!(
             "💥 Template dir path exists: {}",
@@ -176,22 +238,32 @@ pub async fn install_template(src_path: &PathBuf, dest_path: &PathBuf) -> Result
         ));
     }
 
+    prune_excluded_files(dest_path, |path| rules.is_excluded_path(path))?;
+
     // TODO: discuss keeping the tmp dir after install for any purpose, otherwise burn it
 
     Ok(())
 }
 
+/// Where `create_project_dir` puts the project: `dir` (or the current dir) joined with
+/// `project_name`.
 #[tracing::instrument]
-pub async fn create_project_dir(
-    project_name: &str,
-    dir: Option<&str>,
-    overwrite: bool,
-) -> Result<PathBuf> {
+pub fn project_dir_path(project_name: &str, dir: Option<&str>) -> Result<PathBuf> {
     let project_dir = if let Some(dir) = dir {
         PathBuf::from(dir).join(project_name)
     } else {
         env::current_dir()?.join(project_name)
     };
+    Ok(project_dir)
+}
+
+#[tracing::instrument]
+pub async fn create_project_dir(
+    project_name: &str,
+    dir: Option<&str>,
+    overwrite: bool,
+) -> Result<PathBuf> {
+    let project_dir = project_dir_path(project_name, dir)?;
 
     if project_dir.exists() {
         if overwrite {
@@ -220,10 +292,30 @@ pub async fn create_project_dir(
 
 //TODO: add setting to warn from sys_config on directory in paths vec
 //NOTE: for now, just skip
+/// Render every file in the work `dir` in place.
+///
+/// Templates are loaded from `src_dir` (the template's language dir), then the template's
+/// `_common` dir, and named by their path relative to it. That way
+/// `{% include "partials/header.html" %}`, `extends` and `import` resolve across directories and
+/// files with the same name don't collide.
+///
+/// Files matching `[render] exclude` or `.boilignore`, and `[[files]]` whose `when` condition is
+/// false, are deleted first. Files matching `[render] copy_only` or `raw`, binary files and files
+/// that aren't valid UTF-8 are left byte-for-byte as they are. Their paths are still interpolated.
+///
+/// Each file is rendered with the `[syntax]` delimiters that apply to it. Files and dirs named
+/// with a loop marker (`___svc in services___`) are copied once per item first, and the copies
+/// are rendered with the loop variable set.
+///
+/// With `strict_vars`, every variable referenced in the files and file paths that are about to be
+/// rendered must resolve in `ctx`, and undefined values are errors instead of empty strings.
 #[tracing::instrument]
 pub async fn render_template_files(
+    src_dir: &PathBuf,
     dir: &PathBuf,
     ctx: JinjaValue,
+    rules: &RenderRules,
+    strict_vars: bool,
     debug_render: bool,
 ) -> Result<()> {
     info!("Rendering template content...");
@@ -233,162 +325,302 @@ pub async fn render_template_files(
         info!("Template context:\n{ctx:#?}");
     }
 
-    let mut jinja = minijinja::Environment::new();
-    if debug_render {
-        jinja.set_debug(true);
+    let mut src_dirs = vec![src_dir.clone()];
+    src_dirs.extend(rules.common_dir());
+    let env = RenderEnv::new(&src_dirs, rules.syntax(), strict_vars, debug_render);
+
+    let disabled = rules.disabled_paths(env.default_env(), &ctx)?;
+    prune_excluded_files(dir, |name| {
+        rules.is_excluded(name) || is_at_or_under(name, &disabled)
+    })?;
+
+    if strict_vars {
+        let paths = renderable_files(dir, rules, &PathScopes::default(), false)
+            .await?
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect::<Vec<_>>();
+        let undefined = find_undefined_vars(dir, &paths, &env, &ctx)?;
+        if !undefined.is_empty() {
+            let report = undefined
+                .iter()
+                .map(|(name, vars)| format!("  {name}: {}", vars.join(", ")))
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Err(eyre!(
+                "💥 Undefined variables referenced by the template (--strict-vars):\n{report}"
+            ));
+        }
     }
 
-    for path in get_template_paths(dir).await? {
-        if path.is_file() {
-            if debug_render {
-                info!("-------------- Next template... -------------- ");
-                info!("Rendering file: {}", path.display());
-            }
+    let scopes = expand_path_loops(dir, &ctx, env.default_env())?;
 
-            let name = path.file_name().unwrap().to_str().unwrap().to_string();
-            let content = fs::read_to_string(&path)?;
-            jinja.add_template_owned(name.clone(), content)?;
+    let templates = renderable_files(dir, rules, &scopes, debug_render).await?;
+    let started = Instant::now();
+    let timings = par_map(&templates, |(path, name)| {
+        render_file(&env, path, name, &scopes.ctx_for(path, &ctx), debug_render)
+    })
+    .into_iter()
+    .collect::<Result<Vec<_>>>()?;
 
-            if debug_render {
-                info!("[OK] Template added: {}", name);
-            }
+    if debug_render {
+        let mut report = templates
+            .iter()
+            .zip(timings)
+            .map(|((path, _), time)| Ok((template_name(dir, path)?, time)))
+            .collect::<Result<Vec<_>>>()?;
+        report.sort_by_key(|(_, time)| std::cmp::Reverse(*time));
+        let table = format_table(report.into_iter().map(|(file, time)| RenderTiming {
+            file,
+            time: format!("{time:.2?}"),
+        }));
+        info!("Render times:\n{table}");
+    }
+    info!(
+        "Rendered {} files in {:.2?} on {} thread(s)",
+        templates.len(),
+        started.elapsed(),
+        pool_size(templates.len())
+    );
 
-            let template = jinja.get_template(&name)?;
+    info!("Checking for vars in file paths...");
+    interpolate_template_filepaths(dir, &ctx, &scopes, env.default_env()).await?;
 
-            if debug_render {
-                info!("Rendering template: {}", name);
-            }
+    Ok(())
+}
 
-            let rendered = match template.render(&ctx) {
-                Ok(r) => r,
-                Err(e) => {
-                    if debug_render {
-                        // TODO: clean up this long string
-                        return Err(eyre!(
-                            "💥 Failed to render template file {}:\nIt looks like there's an error in your template. No guarantees its not Boiler but I'd check your source, first.\n\n{:#?}",
-                            path.display(),
-                            e
-                        ));
-                    } else {
-                        return Err(eyre!(
-                            "💥 Failed to render template file {}: {}",
-                            path.display(),
-                            e
-                        ));
-                    }
-                }
-            };
+#[derive(Debug, Tabled)]
+struct RenderTiming {
+    #[tabled(rename = "File")]
+    file: String,
+    #[tabled(rename = "Time")]
+    time: String,
+}
 
+/// Render the template named `name` to `path`, returning how long it took.
+fn render_file(
+    env: &RenderEnv,
+    path: &Path,
+    name: &str,
+    ctx: &JinjaValue,
+    debug_render: bool,
+) -> Result<Duration> {
+    let started = Instant::now();
+    let rendered = match env.render(name, ctx) {
+        Ok(r) => r,
+        Err(e) => {
             if debug_render {
-                info!("[OK] Rendered content for {}: No issues.", name);
+                // TODO: clean up this long string
+                return Err(eyre!(
+                    "💥 Failed to render template file {}:\nIt looks like there's an error in your template. No guarantees its not Boiler but I'd check your source, first.\n\n{:#?}",
+                    path.display(),
+                    e
+                ));
+            } else {
+                return Err(eyre!(
+                    "💥 Failed to render template file {}: {}",
+                    path.display(),
+                    e
+                ));
             }
+        }
+    };
+    fs::write(path, rendered)?;
+    Ok(started.elapsed())
+}
 
-            fs::write(&path, rendered)?;
-
+/// The files under `dir` to render, with the template names they were copied from. Files that
+/// are copied as-is are skipped.
+async fn renderable_files(
+    dir: &PathBuf,
+    rules: &RenderRules,
+    scopes: &PathScopes,
+    debug_render: bool,
+) -> Result<Vec<(PathBuf, String)>> {
+    let mut templates: Vec<(PathBuf, String)> = Vec::new();
+    for path in get_template_paths(dir).await? {
+        let name = scopes.origin_name(dir, &path)?;
+        let verbatim = rules.is_copy_only(&name) || rules.is_raw(&name);
+        if verbatim || read_text_file(&path)?.is_none() {
             if debug_render {
-                info!("Wrote rendered content to file: {}", path.display());
+                info!("Copying file without rendering: {}", name);
             }
+            continue;
         }
+        templates.push((path, name));
     }
+    Ok(templates)
+}
 
-    info!("Checking for vars in file paths...");
-    interpolate_template_filepaths(dir, &ctx).await?;
-
-    Ok(())
+/// A Jinja environment that loads templates from the first of `src_dirs` that has them, using
+/// `syntax`, with the built-in filters and functions registered. `strict_vars` makes undefined
+/// values errors.
+#[tracing::instrument]
+pub fn template_env(
+    src_dirs: &[PathBuf],
+    syntax: &SyntaxConfig,
+    strict_vars: bool,
+    debug_render: bool,
+) -> JinjaEnv<'static> {
+    let mut jinja = JinjaEnv::new();
+    register_builtins(&mut jinja);
+    jinja.set_loader(template_loader(src_dirs));
+    jinja.set_syntax(syntax.clone());
+    if strict_vars {
+        jinja.set_undefined_behavior(UndefinedBehavior::Strict);
+    }
+    if debug_render {
+        jinja.set_debug(true);
+    }
+    jinja
 }
 
+/// Name a template by its `/`-separated path relative to `root`.
 #[tracing::instrument]
-pub async fn list_template_files(dir: &PathBuf) -> Result<Vec<PathBuf>> {
-    let files = list_dir(dir)
-        .await?
-        .into_iter()
-        .filter(|p| p.is_file() && !p.to_str().unwrap_or("").contains(".git"))
+pub fn template_name(root: &Path, path: &Path) -> Result<String> {
+    let rel = path
+        .strip_prefix(root)
+        .map_err(|_| eyre!("💥 {} is not inside {}", path.display(), root.display()))?;
+    let segments = rel
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
         .collect::<Vec<_>>();
-    Ok(files)
+    Ok(segments.join("/"))
 }
 
-#[tracing::instrument]
-pub async fn get_template_paths(template_dir: &PathBuf) -> Result<Vec<PathBuf>> {
-    let paths: Vec<PathBuf> = list_dir(template_dir)
-        .await?
-        .iter()
-        .filter(|p| p.is_file())
-        .map(|p| p.to_path_buf())
-        .collect();
-    Ok(paths)
+/// Like `minijinja::path_loader`, but dotfiles (`.gitignore`, `.github/...`) can be loaded.
+/// Only `..`, empty segments and backslashes are rejected so templates can't escape the roots.
+/// Each of `roots` is tried in order, so the language dir wins over `_common`.
+pub fn template_loader(
+    roots: &[PathBuf],
+) -> impl for<'a> Fn(&'a str) -> Result<Option<String>, minijinja::Error> + Send + Sync + 'static {
+    let roots = roots.to_vec();
+    move |name| {
+        if name
+            .split('/')
+            .any(|segment| segment.is_empty() || segment == ".." || segment.contains('\\'))
+        {
+            return Ok(None);
+        }
+        for root in &roots {
+            let path = name
+                .split('/')
+                .fold(root.clone(), |path, segment| path.join(segment));
+            match fs::read_to_string(&path) {
+                Ok(content) => return Ok(Some(content)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    return Err(minijinja::Error::new(
+                        minijinja::ErrorKind::InvalidOperation,
+                        format!("could not read template {}", path.display()),
+                    )
+                    .with_source(e));
+                }
+            }
+        }
+        Ok(None)
+    }
 }
 
+/// Files under `dir` that belong in the search index: everything except `.git` and files
+/// excluded or marked `copy_only` by `rules`.
 #[tracing::instrument]
-pub async fn interpolate_template_filepaths(
-    template_dir: &PathBuf,
-    ctx: &JinjaValue,
-) -> Result<()> {
-    let mut env = JinjaEnv::new();
-
-    for entry in WalkDir::new(template_dir).contents_first(true) {
-        let entry = entry.unwrap();
-        let path = entry.path().to_path_buf();
-        let file_name = path.file_name().unwrap().to_str().unwrap().to_owned();
-        let mut caps_iter = FILEPATH_VARS.captures_iter(&file_name).peekable();
-
-        if caps_iter.peek().is_none() {
+pub async fn list_template_files(dir: &PathBuf, rules: &RenderRules) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in list_dir(dir).await? {
+        if !path.is_file() || is_in_git_dir(dir, &path) {
             continue;
         }
+        if rules.is_indexed(&template_name(rules.root(), &path)?) {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
 
-        // TODO: double-check all vars are interpolated into filename
-        let mut new_path: PathBuf = path.clone();
-        for cap in caps_iter {
-            let var_path_str = if let Some(underscore) = cap.name("underscore") {
-                underscore.as_str()
-            } else if let Some(dash) = cap.name("dash") {
-                dash.as_str()
-            } else {
-                continue;
-            };
-            let target = var_path_str;
-            let var_path_str = var_path_str.trim_matches(['-', '_']);
-
-            let s = format!("{{{{{}}}}}", var_path_str);
-            let template = match env.get_template(&s) {
-                Ok(t) => t,
-                _ => {
-                    env.add_template_owned(s.clone(), s.clone())?;
-                    env.get_template(&s)?
-                }
-            };
+fn is_in_git_dir(root: &Path, path: &Path) -> bool {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .any(|c| c.as_os_str() == ".git")
+}
+
+/// Delete the files under `dir` for which `is_excluded` returns true, along with any directories
+/// left empty. `is_excluded` gets `/`-separated paths relative to `dir`.
+#[tracing::instrument(skip(is_excluded))]
+pub fn prune_excluded_files(dir: &Path, is_excluded: impl Fn(&str) -> bool) -> Result<()> {
+    let mut emptied: Vec<PathBuf> = Vec::new();
 
-            let var_value = template.render(ctx)?;
-            let new_file_name = file_name.replace(target, &var_value);
-            new_path = new_path.with_file_name(new_file_name);
+    for entry in WalkDir::new(dir).min_depth(1) {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
         }
+        let path = entry.path();
+        if is_excluded(&template_name(dir, path)?) {
+            fs::remove_file(path)?;
+            emptied.extend(
+                path.ancestors()
+                    .skip(1)
+                    .take_while(|p| *p != dir)
+                    .map(Path::to_path_buf),
+            );
+        }
+    }
 
-        move_file(&path, &new_path).await?;
+    emptied.sort_by_key(|p| std::cmp::Reverse(p.components().count()));
+    emptied.dedup();
+    for path in emptied {
+        if fs::read_dir(&path)?.next().is_none() {
+            fs::remove_dir(&path)?;
+        }
     }
 
     Ok(())
 }
 
-/// Render a single variable using minijinja.
+#[tracing::instrument]
+pub async fn get_template_paths(template_dir: &PathBuf) -> Result<Vec<PathBuf>> {
+    let paths: Vec<PathBuf> = list_dir(template_dir)
+        .await?
+        .iter()
+        .filter(|p| p.is_file())
+        .map(|p| p.to_path_buf())
+        .collect();
+    Ok(paths)
+}
+
+lazy_static! {
+    static ref VAR_ENV: JinjaEnv<'static> = {
+        let mut jinja = JinjaEnv::new();
+        register_builtins(&mut jinja);
+        jinja
+    };
+}
+
+/// Render a single variable (or any expression) using minijinja, with the built-in filters and
+/// functions.
 ///
-/// Note: this function create a new Jinja Environment each time it's called.
-/// If you're doing anything serious, use minimjinja directly, and set up an
-/// environment once and add templates to it.
+/// Expressions don't depend on the template's delimiters, so every call shares one environment.
+/// To render files, use `RenderEnv`.
 ///
 /// # Example
 ///
 /// ```rust
-/// use minijinja::{context, Environment as JinjaEnv};
+/// use minijinja::context;
 ///
 /// use boilermaker_core::template::render_var;
 ///
 /// let ctx = context! { a => context! { b => "Hello, World!" } };
 /// let rendered = render_var("a.b", &ctx).unwrap();
 /// assert_eq!(rendered, "Hello, World!");
+///
+/// let rendered = render_var("a.b|upper", &ctx).unwrap();
+/// assert_eq!(rendered, "HELLO, WORLD!");
 /// ```
-// TODO: make a global JinjaEnv to avoid recreating it each time
 #[tracing::instrument]
 pub fn render_var(path: &str, ctx: &JinjaValue) -> Result<String> {
-    Ok(JinjaEnv::new().render_str(&format!("{{{{ {} }}}}", path), ctx)?)
+    Ok(VAR_ENV.compile_expression(path)?.eval(ctx)?.to_string())
 }
 
 // TODO: find a simpler way to do this with less boilerplate (simple to->from?)
@@ -419,15 +651,15 @@ pub fn make_install_cmd(t: &impl InstallableTemplate) -> String {
     cmd
 }
 
+/// Remove every dir in `dir` except the `keep` language dirs and `_common`.
 #[tracing::instrument]
-pub fn remove_other_langs(install: &InstallConfig) -> Result<()> {
-    let keep = install.lang.as_str();
-    for entry in fs::read_dir(&install.work_dir)? {
+pub fn remove_other_langs(dir: &Path, keep: &[String]) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
         let entry = entry?;
         let path = entry.path();
         if path.is_dir() {
             let dir_name = path.file_name().unwrap().to_string_lossy();
-            if keep == dir_name.as_ref() {
+            if dir_name == COMMON_DIR || keep.iter().any(|lang| *lang == dir_name) {
                 continue;
             }
             std::fs::remove_dir_all(&path)?;
@@ -447,6 +679,7 @@ pub struct InstallConfig {
     pub work_dir: PathBuf,
     pub sha256_hash: Option<String>,
     pub template_dir: Option<PathBuf>,
+    pub commit_sha: Option<String>,
 }
 
 impl InstallConfig {
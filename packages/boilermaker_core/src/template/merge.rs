@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+
+use minijinja::value::{Value as JinjaValue, ValueKind};
+
+use crate::config::TemplateConfigMerge;
+
+/// Recursively merge context layers, later layers winning.
+///
+/// Nested maps are merged key by key instead of being replaced wholesale. Lists are replaced,
+/// unless their dot-delimited path is listed in `[merge] append`, in which case later items are
+/// appended.
+#[tracing::instrument]
+pub fn deep_merge(contexts: Vec<JinjaValue>, opts: Option<&TemplateConfigMerge>) -> JinjaValue {
+    let append = opts.map(|o| o.append.as_slice()).unwrap_or_default();
+
+    contexts.into_iter().fold(
+        JinjaValue::from_serialize(BTreeMap::<String, JinjaValue>::new()),
+        |base, over| merge_values(base, over, "", append),
+    )
+}
+
+fn merge_values(base: JinjaValue, over: JinjaValue, path: &str, append: &[String]) -> JinjaValue {
+    if over.is_undefined() {
+        return base;
+    }
+
+    match (base.kind(), over.kind()) {
+        (ValueKind::Map, ValueKind::Map) => {
+            let mut merged: BTreeMap<String, JinjaValue> = BTreeMap::new();
+            for (key, value) in map_entries(&base) {
+                merged.insert(key, value);
+            }
+            for (key, value) in map_entries(&over) {
+                let key_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                let value = match merged.remove(&key) {
+                    Some(existing) => merge_values(existing, value, &key_path, append),
+                    None => value,
+                };
+                merged.insert(key, value);
+            }
+            JinjaValue::from_serialize(merged)
+        }
+        (ValueKind::Seq, ValueKind::Seq) if append.iter().any(|p| p == path) => {
+            let items = base
+                .try_iter()
+                .into_iter()
+                .flatten()
+                .chain(over.try_iter().into_iter().flatten())
+                .collect::<Vec<_>>();
+            JinjaValue::from(items)
+        }
+        _ => over,
+    }
+}
+
+fn map_entries(v: &JinjaValue) -> Vec<(String, JinjaValue)> {
+    let Ok(keys) = v.try_iter() else {
+        return vec![];
+    };
+    keys.filter_map(|k| {
+        let value = v.get_item(&k).ok()?;
+        Some((k.as_str()?.to_owned(), value))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use minijinja::context;
+
+    use super::*;
+
+    fn merge_opts(append: &[&str]) -> TemplateConfigMerge {
+        TemplateConfigMerge {
+            append: append.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let merged = deep_merge(
+            vec![context!(name => "a", port => 1), context!(port => 2)],
+            None,
+        );
+        assert_eq!(merged.get_attr("name").unwrap().as_str(), Some("a"));
+        assert_eq!(merged.get_attr("port").unwrap().as_i64(), Some(2));
+    }
+
+    #[test]
+    fn nested_maps_are_merged_by_key() {
+        let merged = deep_merge(
+            vec![
+                context!(db => context!(host => "localhost", port => 5432)),
+                context!(db => context!(port => 6543)),
+            ],
+            None,
+        );
+        let db = merged.get_attr("db").unwrap();
+        assert_eq!(db.get_attr("host").unwrap().as_str(), Some("localhost"));
+        assert_eq!(db.get_attr("port").unwrap().as_i64(), Some(6543));
+    }
+
+    #[test]
+    fn lists_are_replaced_by_default() {
+        let merged = deep_merge(
+            vec![
+                context!(deps => vec!["a", "b"]),
+                context!(deps => vec!["c"]),
+            ],
+            None,
+        );
+        assert_eq!(merged.get_attr("deps").unwrap().to_string(), r#"["c"]"#);
+    }
+
+    #[test]
+    fn listed_paths_are_appended() {
+        let opts = merge_opts(&["deps", "db.hosts"]);
+        let merged = deep_merge(
+            vec![
+                context!(deps => vec!["a"], db => context!(hosts => vec!["h1"]), other => vec!["x"]),
+                context!(deps => vec!["b"], db => context!(hosts => vec!["h2"]), other => vec!["y"]),
+            ],
+            Some(&opts),
+        );
+        assert_eq!(
+            merged.get_attr("deps").unwrap().to_string(),
+            r#"["a", "b"]"#
+        );
+        let hosts = merged.get_attr("db").unwrap().get_attr("hosts").unwrap();
+        assert_eq!(hosts.to_string(), r#"["h1", "h2"]"#);
+        assert_eq!(merged.get_attr("other").unwrap().to_string(), r#"["y"]"#);
+    }
+
+    #[test]
+    fn append_only_matches_the_full_path() {
+        let opts = merge_opts(&["hosts"]);
+        let merged = deep_merge(
+            vec![
+                context!(db => context!(hosts => vec!["h1"])),
+                context!(db => context!(hosts => vec!["h2"])),
+            ],
+            Some(&opts),
+        );
+        let hosts = merged.get_attr("db").unwrap().get_attr("hosts").unwrap();
+        assert_eq!(hosts.to_string(), r#"["h2"]"#);
+    }
+
+    #[test]
+    fn non_list_values_replace_lists_even_when_appending() {
+        let opts = merge_opts(&["deps"]);
+        let merged = deep_merge(
+            vec![context!(deps => vec!["a"]), context!(deps => "none")],
+            Some(&opts),
+        );
+        assert_eq!(merged.get_attr("deps").unwrap().as_str(), Some("none"));
+    }
+
+    #[test]
+    fn undefined_layers_are_ignored() {
+        let merged = deep_merge(vec![context!(name => "a"), JinjaValue::UNDEFINED], None);
+        assert_eq!(merged.get_attr("name").unwrap().as_str(), Some("a"));
+    }
+}
@@ -1,4 +1,35 @@
+pub mod answers;
+pub mod apply;
+pub mod computed;
+pub mod context;
+pub mod filters;
+pub mod hooks;
 pub mod lib;
+pub mod merge;
+pub mod paths;
+pub mod plan;
+pub mod prompt;
+pub mod render;
+pub mod requires;
+pub mod rules;
+pub mod schema;
 pub mod static_analysis;
+pub mod syntax;
+pub mod upgrade;
 
+pub use answers::{Answers, ProjectLang, read_answers, write_answers};
+pub use apply::{ApplyEntry, ConflictPolicy, apply_project};
+pub use computed::apply_computed_vars;
+pub use filters::register_builtins;
+pub use hooks::{confirm_hooks, run_check_hooks, run_hooks};
 pub use lib::*;
+pub use merge::deep_merge;
+pub use paths::{PathMarker, interpolate_template_filepaths, path_markers};
+pub use plan::{PlanAction, PlanEntry, diff_buffers, diff_project, plan_project};
+pub use prompt::prompt_for_vars;
+pub use render::RenderEnv;
+pub use requires::check_requirements;
+pub use rules::RenderRules;
+pub use schema::{apply_var_schema, coerce_to_defaults};
+pub use syntax::TemplateSyntax;
+pub use upgrade::merge_project;
@@ -0,0 +1,372 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{Result, eyre::eyre};
+use minijinja::{
+    Environment as JinjaEnv,
+    value::{Value as JinjaValue, ValueKind, merge_maps},
+};
+use walkdir::WalkDir;
+
+use crate::{
+    constants::{
+        TEMPLATE_FILEPATH_VAR_PATTERN as FILEPATH_VARS, TEMPLATE_PATH_LOOP_PATTERN as PATH_LOOP,
+    },
+    template::template_name,
+    util::file::{copy_path, move_file},
+};
+
+/// A `___x___`/`---x---` marker in a file or dir name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathMarker<'a> {
+    /// `___expr___`: replaced with the rendered expression, e.g. `___config.org|snake_case___`.
+    Expr { marker: &'a str, expr: &'a str },
+    /// `___var in list___` or `___expr for var in list___`: one copy per item of `list`, named
+    /// by `expr` (`var` by default), with `var` set in everything below it.
+    Loop {
+        marker: &'a str,
+        expr: &'a str,
+        var: &'a str,
+        list: &'a str,
+    },
+}
+
+/// The path markers in the file or dir name `name`, in order.
+pub fn path_markers(name: &str) -> Vec<PathMarker<'_>> {
+    FILEPATH_VARS
+        .find_iter(name)
+        .map(|m| {
+            let marker = m.as_str();
+            let inner = marker.trim_matches(['-', '_']);
+            match PATH_LOOP.captures(inner) {
+                Some(caps) => {
+                    let var = caps.name("var").map_or("", |v| v.as_str());
+                    PathMarker::Loop {
+                        marker,
+                        expr: caps.name("expr").map_or(var, |e| e.as_str()),
+                        var,
+                        list: caps.name("list").map_or("", |l| l.as_str()),
+                    }
+                }
+                None => PathMarker::Expr {
+                    marker,
+                    expr: inner,
+                },
+            }
+        })
+        .collect()
+}
+
+/// The variables set by loop markers in the template named `name` and the dirs above it.
+pub fn loop_vars(name: &str) -> HashSet<String> {
+    name.split('/')
+        .flat_map(path_markers)
+        .filter_map(|m| match m {
+            PathMarker::Loop { var, .. } => Some(var.to_owned()),
+            PathMarker::Expr { .. } => None,
+        })
+        .collect()
+}
+
+/// The loop variables in effect under each copy made by `expand_path_loops`.
+#[derive(Debug, Default)]
+pub struct PathScopes(BTreeMap<PathBuf, PathScope>);
+
+#[derive(Debug)]
+struct PathScope {
+    ctx: JinjaValue,
+    origin: String,
+}
+
+impl PathScopes {
+    /// The context for `path`: `ctx` plus the loop variables of the copies it's in.
+    pub fn ctx_for(&self, path: &Path, ctx: &JinjaValue) -> JinjaValue {
+        self.scope_for(path)
+            .map_or_else(|| ctx.clone(), |(_, scope)| scope.ctx.clone())
+    }
+
+    /// The template name (see `template_name`) of the file `path` was copied from. `[render]`
+    /// rules and `[syntax]` overrides match against it, and the source template is loaded by it.
+    pub fn origin_name(&self, root: &Path, path: &Path) -> Result<String> {
+        match self.scope_for(path) {
+            Some((dir, scope)) => {
+                let rest = template_name(dir, path)?;
+                Ok(if rest.is_empty() {
+                    scope.origin.clone()
+                } else {
+                    format!("{}/{rest}", scope.origin)
+                })
+            }
+            None => template_name(root, path),
+        }
+    }
+
+    fn scope_for(&self, path: &Path) -> Option<(&Path, &PathScope)> {
+        path.ancestors().find_map(|p| {
+            self.0
+                .get_key_value(p)
+                .map(|(dir, scope)| (dir.as_path(), scope))
+        })
+    }
+}
+
+/// Expand the loop markers under `dir`: each `___svc in services___` file or dir is copied once
+/// per item of `services` and the original is removed. An undefined or empty list leaves no
+/// copies.
+#[tracing::instrument(skip(env))]
+pub fn expand_path_loops(dir: &Path, ctx: &JinjaValue, env: &JinjaEnv) -> Result<PathScopes> {
+    let mut scopes = PathScopes::default();
+    expand_dir(dir, ctx, "", env, &mut scopes)?;
+    Ok(scopes)
+}
+
+fn expand_dir(
+    dir: &Path,
+    ctx: &JinjaValue,
+    origin: &str,
+    env: &JinjaEnv,
+    scopes: &mut PathScopes,
+) -> Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+
+    for path in entries {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let entry_origin = if origin.is_empty() {
+            name.clone()
+        } else {
+            format!("{origin}/{name}")
+        };
+
+        let loops = path_markers(&name)
+            .into_iter()
+            .filter(|m| matches!(m, PathMarker::Loop { .. }))
+            .collect::<Vec<_>>();
+        let Some(PathMarker::Loop {
+            marker,
+            expr,
+            var,
+            list,
+        }) = loops.first()
+        else {
+            if path.is_dir() {
+                expand_dir(&path, ctx, &entry_origin, env, scopes)?;
+            }
+            continue;
+        };
+        if loops.len() > 1 {
+            return Err(eyre!(
+                "💥 {entry_origin}: only one loop marker is allowed per name."
+            ));
+        }
+
+        let items = env
+            .compile_expression(list)
+            .and_then(|e| e.eval(ctx))
+            .map_err(|e| eyre!("💥 Failed to evaluate `{list}` in {entry_origin}: {e}"))?;
+        let items = match items.kind() {
+            ValueKind::Undefined | ValueKind::None => Vec::new(),
+            ValueKind::Seq | ValueKind::Map | ValueKind::Iterable => items.try_iter()?.collect(),
+            _ => {
+                return Err(eyre!(
+                    "💥 `{list}` in {entry_origin} must be a list, not {}: {items}",
+                    items.kind()
+                ));
+            }
+        };
+
+        for item in items {
+            let item_ctx = merge_maps([
+                ctx.clone(),
+                JinjaValue::from(BTreeMap::from([(var.to_string(), item)])),
+            ]);
+            let segment = render_marker(env, marker, expr, &item_ctx, &entry_origin)?;
+            let target = path.with_file_name(name.replacen(marker, &segment, 1));
+            if target.exists() {
+                return Err(eyre!(
+                    "💥 {entry_origin}: more than one item renders to {}",
+                    target.display()
+                ));
+            }
+            copy_path(&path, &target)?;
+            if target.is_dir() {
+                expand_dir(&target, &item_ctx, &entry_origin, env, scopes)?;
+            }
+            scopes.0.insert(
+                target,
+                PathScope {
+                    ctx: item_ctx,
+                    origin: entry_origin.clone(),
+                },
+            );
+        }
+
+        if path.is_dir() {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Replace the `___x___`/`---x---` markers in every file and dir name under `template_dir`.
+/// Loop markers must already be expanded by `expand_path_loops`.
+#[tracing::instrument(skip(env))]
+pub async fn interpolate_template_filepaths(
+    template_dir: &PathBuf,
+    ctx: &JinjaValue,
+    scopes: &PathScopes,
+    env: &JinjaEnv<'_>,
+) -> Result<()> {
+    for entry in WalkDir::new(template_dir).min_depth(1).contents_first(true) {
+        let entry = entry?;
+        let path = entry.path().to_path_buf();
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let markers = path_markers(&file_name);
+        if markers.is_empty() {
+            continue;
+        }
+
+        let origin = scopes.origin_name(template_dir, &path)?;
+        let ctx = scopes.ctx_for(&path, ctx);
+        let mut new_name = file_name.clone();
+        for marker in markers {
+            let PathMarker::Expr { marker, expr } = marker else {
+                continue;
+            };
+            let value = render_marker(env, marker, expr, &ctx, &origin)?;
+            new_name = new_name.replacen(marker, &value, 1);
+        }
+
+        let new_path = path.with_file_name(&new_name);
+        if new_path.exists() {
+            return Err(eyre!(
+                "💥 {origin} renders to {}, which already exists.",
+                new_path.display()
+            ));
+        }
+        move_file(&path, &new_path).await?;
+    }
+
+    Ok(())
+}
+
+/// Render a marker's expression, checking the result can be used as a single path segment.
+fn render_marker(
+    env: &JinjaEnv,
+    marker: &str,
+    expr: &str,
+    ctx: &JinjaValue,
+    origin: &str,
+) -> Result<String> {
+    let value = env
+        .compile_expression(expr)
+        .and_then(|e| e.eval(ctx))
+        .map_err(|e| eyre!("💥 Failed to render `{marker}` in {origin}: {e}"))?
+        .to_string();
+    if value.trim().is_empty() || value.contains(['/', '\\']) || value.contains("..") {
+        return Err(eyre!(
+            "💥 `{marker}` in {origin} renders to {value:?}. Path markers can't be empty or contain `/` or `..`."
+        ));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use minijinja::context;
+
+    use super::*;
+
+    fn render(expr: &str, ctx: JinjaValue) -> Result<String> {
+        render_marker(&JinjaEnv::new(), "___x___", expr, &ctx, "test")
+    }
+
+    #[test]
+    fn parses_expr_markers() {
+        assert_eq!(
+            path_markers("___name___.rs"),
+            vec![PathMarker::Expr {
+                marker: "___name___",
+                expr: "name",
+            }]
+        );
+        assert_eq!(
+            path_markers("---org|snake_case---"),
+            vec![PathMarker::Expr {
+                marker: "---org|snake_case---",
+                expr: "org|snake_case",
+            }]
+        );
+        assert!(path_markers("plain_name.rs").is_empty());
+    }
+
+    #[test]
+    fn parses_loop_markers() {
+        assert_eq!(
+            path_markers("___svc in services___"),
+            vec![PathMarker::Loop {
+                marker: "___svc in services___",
+                expr: "svc",
+                var: "svc",
+                list: "services",
+            }]
+        );
+        assert_eq!(
+            path_markers("___svc.name for svc in config.services___.yml"),
+            vec![PathMarker::Loop {
+                marker: "___svc.name for svc in config.services___",
+                expr: "svc.name",
+                var: "svc",
+                list: "config.services",
+            }]
+        );
+    }
+
+    #[test]
+    fn collects_loop_vars_from_every_segment() {
+        let vars = loop_vars("___svc in services___/___env in envs___/___svc___-___env___.yml");
+        assert_eq!(vars, HashSet::from(["svc".to_string(), "env".to_string()]));
+        assert!(loop_vars("src/___name___.rs").is_empty());
+    }
+
+    #[test]
+    fn renders_marker_expressions() {
+        let value = render("name ~ '-' ~ n", context!(name => "api", n => 2)).unwrap();
+        assert_eq!(value, "api-2");
+    }
+
+    #[test]
+    fn rejects_empty_values() {
+        assert!(render("name", context!(name => "")).is_err());
+        assert!(render("name", context!(name => "  ")).is_err());
+    }
+
+    #[test]
+    fn rejects_separators() {
+        assert!(render("name", context!(name => "a/b")).is_err());
+        assert!(render("name", context!(name => "a\\b")).is_err());
+        assert!(render("name", context!(name => "/abs")).is_err());
+    }
+
+    #[test]
+    fn rejects_parent_dirs() {
+        assert!(render("name", context!(name => "..")).is_err());
+        assert!(render("name", context!(name => "a..b")).is_err());
+        let err = render("name", context!(name => "../escape")).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("can't be empty or contain `/` or `..`")
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    collections::BTreeSet,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{Result, eyre::eyre};
+use git2::Patch;
+use tabled::Tabled;
+use walkdir::WalkDir;
+
+use crate::template::template_name;
+
+/// What generating a project would do to a path in the project dir.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PlanAction {
+    Create,
+    Overwrite,
+    Unchanged,
+    /// Only in the existing project dir, which `--overwrite` clears first.
+    Delete,
+}
+
+impl fmt::Display for PlanAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            PlanAction::Create => "create",
+            PlanAction::Overwrite => "overwrite",
+            PlanAction::Unchanged => "unchanged",
+            PlanAction::Delete => "delete",
+        };
+        write!(f, "{s}")
+    }
+}
+
+#[derive(Debug, Clone, Tabled)]
+pub struct PlanEntry {
+    #[tabled(rename = "Action")]
+    pub action: PlanAction,
+    /// `/`-separated path relative to the project dir. Directories end with `/`.
+    #[tabled(rename = "Path")]
+    pub path: String,
+}
+
+/// Compare the rendered project in `work_dir` with `project_dir`, file by file.
+///
+/// Files only in `project_dir` are listed as `Delete`; whole directories that the rendered
+/// project doesn't have are listed once.
+#[tracing::instrument]
+pub fn plan_project(work_dir: &Path, project_dir: &Path) -> Result<Vec<PlanEntry>> {
+    let mut plan: Vec<PlanEntry> = Vec::new();
+    let mut rendered: BTreeSet<String> = BTreeSet::new();
+
+    for entry in WalkDir::new(work_dir).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        let path = template_name(work_dir, entry.path())?;
+        if !entry.file_type().is_file() {
+            rendered.insert(format!("{path}/"));
+            continue;
+        }
+
+        let target = project_dir.join(&path);
+        let action = if !target.is_file() {
+            PlanAction::Create
+        } else if fs::read(&target)? == fs::read(entry.path())? {
+            PlanAction::Unchanged
+        } else {
+            PlanAction::Overwrite
+        };
+        rendered.insert(path.clone());
+        plan.push(PlanEntry { action, path });
+    }
+
+    if project_dir.is_dir() {
+        let mut walker = WalkDir::new(project_dir)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter();
+        while let Some(entry) = walker.next() {
+            let entry = entry?;
+            let mut path = template_name(project_dir, entry.path())?;
+            let is_dir = entry.file_type().is_dir();
+            if is_dir {
+                path.push('/');
+            }
+            if rendered.contains(&path) {
+                continue;
+            }
+            if is_dir {
+                walker.skip_current_dir();
+            }
+            plan.push(PlanEntry {
+                action: PlanAction::Delete,
+                path,
+            });
+        }
+    }
+
+    plan.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(plan)
+}
+
+/// A unified diff from `project_dir` to the rendered project in `work_dir`, for every entry in
+/// `plan` that would change something.
+#[tracing::instrument(skip(plan))]
+pub fn diff_project(work_dir: &Path, project_dir: &Path, plan: &[PlanEntry]) -> Result<String> {
+    let mut out = String::new();
+
+    for entry in plan {
+        let read = |dir: &Path| -> Result<Vec<u8>> {
+            let path = dir.join(&entry.path);
+            Ok(if path.is_file() {
+                fs::read(path)?
+            } else {
+                vec![]
+            })
+        };
+
+        let (old, new) = match entry.action {
+            PlanAction::Unchanged => continue,
+            PlanAction::Delete if entry.path.ends_with('/') => {
+                out.push_str(&format!(
+                    "Only in {}: {}\n",
+                    project_dir.display(),
+                    entry.path
+                ));
+                continue;
+            }
+            PlanAction::Create => (vec![], read(work_dir)?),
+            PlanAction::Overwrite => (read(project_dir)?, read(work_dir)?),
+            PlanAction::Delete => (read(project_dir)?, vec![]),
+        };
+
+        out.push_str(&diff_buffers(&entry.path, &old, &new)?);
+    }
+
+    Ok(out)
+}
+
+/// A unified diff of the file at `path` from `old` to `new`.
+pub fn diff_buffers(path: &str, old: &[u8], new: &[u8]) -> Result<String> {
+    let name = PathBuf::from(path);
+    let mut patch = Patch::from_buffers(old, Some(&name), new, Some(&name), None)
+        .map_err(|e| eyre!("💥 Failed to diff {path}: {e}"))?;
+    let buf = patch.to_buf()?;
+    Ok(String::from_utf8_lossy(&buf).to_string())
+}
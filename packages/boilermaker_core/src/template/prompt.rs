@@ -0,0 +1,131 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use color_eyre::Result;
+use minijinja::value::{Value as JinjaValue, ValueKind};
+use tracing::warn;
+
+use crate::{
+    config::{TemplateConfigPrompt, TemplateConfigVarSchema},
+    template::schema::{coerce_to_kind, coerce_var, format_choices, validate_var},
+    util::io::prompt_input,
+};
+
+/// Reserved key under `[variables]` holding variable profiles. Never prompted for.
+const PROFILES_KEY: &str = "profiles";
+
+/// Ask the user for a value for each declared top-level variable.
+///
+/// Variables come from the (profile-merged) context plus any extra names declared in
+/// `[prompts]` or `[schema]`. Nested tables are skipped, as are any names in `skip` (i.e. vars
+/// already passed on the command line). Empty input keeps the default.
+#[tracing::instrument]
+pub fn prompt_for_vars(
+    ctx: &JinjaValue,
+    prompts: Option<&HashMap<String, TemplateConfigPrompt>>,
+    schema: Option<&HashMap<String, TemplateConfigVarSchema>>,
+    skip: &HashSet<String>,
+) -> Result<JinjaValue> {
+    let mut defaults: BTreeMap<String, JinjaValue> = BTreeMap::new();
+    if let Ok(keys) = ctx.try_iter() {
+        for key in keys {
+            let Some(name) = key.as_str() else {
+                continue;
+            };
+            defaults.insert(name.to_owned(), ctx.get_item(&key)?);
+        }
+    }
+    let extra_names = prompts
+        .into_iter()
+        .flat_map(|p| p.keys())
+        .chain(schema.into_iter().flat_map(|s| s.keys()));
+    for name in extra_names {
+        defaults
+            .entry(name.to_owned())
+            .or_insert(JinjaValue::UNDEFINED);
+    }
+
+    let mut answers: BTreeMap<String, JinjaValue> = BTreeMap::new();
+    for (name, default) in defaults {
+        let var_schema = schema.and_then(|s| s.get(&name));
+        if name == PROFILES_KEY || skip.contains(&name) || !is_promptable(&default, var_schema) {
+            continue;
+        }
+
+        let meta = prompts
+            .and_then(|p| p.get(&name))
+            .cloned()
+            .unwrap_or_default();
+        if let Some(value) = prompt_for_var(&name, &default, &meta, var_schema)? {
+            answers.insert(name, value);
+        }
+    }
+
+    Ok(JinjaValue::from_serialize(answers))
+}
+
+#[tracing::instrument]
+fn prompt_for_var(
+    name: &str,
+    default: &JinjaValue,
+    meta: &TemplateConfigPrompt,
+    var_schema: Option<&TemplateConfigVarSchema>,
+) -> Result<Option<JinjaValue>> {
+    if let Some(help) = &meta.help {
+        println!("  {help}");
+    }
+
+    let mut message = meta.message.as_deref().unwrap_or(name).to_owned();
+    if let Some(choices) = var_schema.and_then(|s| s.choices.as_ref()) {
+        message.push_str(&format!(" ({})", format_choices(choices)));
+    }
+    let has_default = !(default.is_undefined() || default.is_none());
+    let prompt = if has_default {
+        format!("{message} [{default}]: ")
+    } else {
+        format!("{message}: ")
+    };
+
+    loop {
+        let input = prompt_input(&prompt)?;
+        if input.is_empty() {
+            let required = var_schema.is_some_and(|s| s.required);
+            if !has_default && (var_schema.is_none() || required) {
+                warn!("A value is required for `{name}`.");
+                continue;
+            }
+            return Ok(None);
+        }
+
+        let Some(var_schema) = var_schema else {
+            match coerce_to_kind(&input, default) {
+                Some(value) => return Ok(Some(value)),
+                None => warn!(
+                    "Invalid value for `{name}`: expected a {} like the default.",
+                    default.kind()
+                ),
+            }
+            continue;
+        };
+
+        let checked = coerce_var(&JinjaValue::from(input), var_schema)
+            .and_then(|v| validate_var(&v, var_schema).map(|_| v));
+        match checked {
+            Ok(value) => return Ok(Some(value)),
+            Err(e) => warn!("Invalid value for `{name}`: {e}"),
+        }
+    }
+}
+
+/// Only scalars (and lists with a declared schema, as comma-separated input) can be answered
+/// from a single line of input.
+fn is_promptable(v: &JinjaValue, var_schema: Option<&TemplateConfigVarSchema>) -> bool {
+    match v.kind() {
+        ValueKind::Undefined
+        | ValueKind::None
+        | ValueKind::Bool
+        | ValueKind::Number
+        | ValueKind::String => true,
+        ValueKind::Seq => var_schema.is_some(),
+        _ => false,
+    }
+}
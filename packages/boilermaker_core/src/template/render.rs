@@ -0,0 +1,52 @@
+use std::path::PathBuf;
+
+use minijinja::{Environment as JinjaEnv, value::Value as JinjaValue};
+
+use crate::template::{TemplateSyntax, template_env};
+
+/// The Jinja environment for one generation: built once, with the template loader and the
+/// built-in filters and functions, and shared by every file rendered (across threads, too).
+///
+/// minijinja sets delimiters per environment, so there's one environment for each of the
+/// template's `[syntax]` delimiter sets. Loaded templates are cached, so a file parsed by the
+/// `--strict-vars` check isn't parsed again to render it.
+#[derive(Debug)]
+pub struct RenderEnv {
+    envs: Vec<JinjaEnv<'static>>,
+    syntax: TemplateSyntax,
+}
+
+impl RenderEnv {
+    #[tracing::instrument]
+    pub fn new(
+        src_dirs: &[PathBuf],
+        syntax: &TemplateSyntax,
+        strict_vars: bool,
+        debug_render: bool,
+    ) -> Self {
+        RenderEnv {
+            envs: syntax
+                .configs()
+                .iter()
+                .map(|config| template_env(src_dirs, config, strict_vars, debug_render))
+                .collect(),
+            syntax: syntax.clone(),
+        }
+    }
+
+    /// The environment with the template's default delimiters. Expressions (`[[files]] when`,
+    /// path markers) don't depend on delimiters and are evaluated with it.
+    pub fn default_env(&self) -> &JinjaEnv<'static> {
+        &self.envs[0]
+    }
+
+    /// The environment for the template named `name` (see `template_name`).
+    pub fn env_for(&self, name: &str) -> &JinjaEnv<'static> {
+        &self.envs[self.syntax.index_for(name)]
+    }
+
+    /// Render the template named `name` with `ctx`.
+    pub fn render(&self, name: &str, ctx: &JinjaValue) -> Result<String, minijinja::Error> {
+        self.env_for(name).get_template(name)?.render(ctx)
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::config::TemplateConfigRequires;
+
+lazy_static! {
+    static ref VERSION_PATTERN: Regex = Regex::new(r"\d+(\.\d+)*").unwrap();
+}
+
+/// Check `[requires]`, returning a line for every command, version or env var that's missing.
+#[tracing::instrument]
+pub fn check_requirements(requires: &TemplateConfigRequires) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let mut commands = requires.commands.iter().collect::<Vec<_>>();
+    commands.extend(
+        requires
+            .min_versions
+            .keys()
+            .filter(|c| !requires.commands.contains(c)),
+    );
+    commands.sort();
+
+    for command in commands {
+        if find_command(command).is_none() {
+            problems.push(format!("command not found: {command}"));
+            continue;
+        }
+
+        let Some(min_version) = requires.min_versions.get(command) else {
+            continue;
+        };
+        match command_version(command) {
+            Some(version) if compare_versions(&version, min_version).is_lt() => problems.push(
+                format!("{command} {version} is installed, but {min_version} or newer is required"),
+            ),
+            Some(_) => {}
+            None => problems.push(format!(
+                "couldn't read a version from `{command} --version` (need {min_version} or newer)"
+            )),
+        }
+    }
+
+    for name in &requires.env {
+        if env::var_os(name).is_none_or(|v| v.is_empty()) {
+            problems.push(format!("environment variable not set: {name}"));
+        }
+    }
+
+    problems
+}
+
+/// Look for an executable named `name` on the `PATH`.
+pub fn find_command(name: &str) -> Option<PathBuf> {
+    if name.contains(['/', '\\']) {
+        let path = PathBuf::from(name);
+        return is_executable(&path).then_some(path);
+    }
+
+    let exts = if cfg!(windows) {
+        env::var("PATHEXT")
+            .unwrap_or(".EXE;.CMD;.BAT".to_owned())
+            .split(';')
+            .map(str::to_owned)
+            .collect::<Vec<_>>()
+    } else {
+        vec![]
+    };
+
+    env::split_paths(&env::var_os("PATH")?).find_map(|dir| {
+        std::iter::once(dir.join(name))
+            .chain(exts.iter().map(|ext| dir.join(format!("{name}{ext}"))))
+            .find(|p| is_executable(p))
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// The version reported by `<command> --version`, read with `parse_version`.
+fn command_version(command: &str) -> Option<String> {
+    let output = Command::new(command).arg("--version").output().ok()?;
+    parse_version(&format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    ))
+}
+
+/// The first version-looking number in `text`, e.g. `20.11.1` from `v20.11.1` or `1.80.0` from
+/// `cargo 1.80.0 (376290515 2024-07-16)`.
+fn parse_version(text: &str) -> Option<String> {
+    VERSION_PATTERN.find(text).map(|m| m.as_str().to_owned())
+}
+
+/// Compare dotted versions numerically. Missing parts count as 0, so `20` == `20.0.0`.
+fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    let parts = |v: &str| {
+        v.trim_start_matches('v')
+            .split('.')
+            .map(|p| p.parse::<u64>().unwrap_or(0))
+            .collect::<Vec<_>>()
+    };
+    let (a, b) = (parts(a), parts(b));
+    let len = a.len().max(b.len());
+    (0..len)
+        .map(|i| {
+            let x = a.get(i).copied().unwrap_or(0);
+            let y = b.get(i).copied().unwrap_or(0);
+            x.cmp(&y)
+        })
+        .find(|o| o.is_ne())
+        .unwrap_or(std::cmp::Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::*;
+
+    #[test]
+    fn parses_versions_from_version_output() {
+        let cases = [
+            ("v20.11.1\n", "20.11.1"),
+            ("cargo 1.80.0 (376290515 2024-07-16)", "1.80.0"),
+            ("Python 3.12.1", "3.12.1"),
+            ("go version go1.22.0 linux/amd64", "1.22.0"),
+            ("\nDocker version 24.0.7, build afdd53b", "24.0.7"),
+            ("tool 7", "7"),
+        ];
+        for (output, version) in cases {
+            assert_eq!(
+                parse_version(output).as_deref(),
+                Some(version),
+                "{output:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn no_version_in_output() {
+        assert_eq!(parse_version(""), None);
+        assert_eq!(parse_version("unknown option: --version"), None);
+    }
+
+    #[test]
+    fn compares_parts_numerically() {
+        assert_eq!(compare_versions("18.19.0", "20"), Ordering::Less);
+        assert_eq!(compare_versions("20.11.1", "20"), Ordering::Greater);
+        assert_eq!(compare_versions("1.10.0", "1.9.9"), Ordering::Greater);
+        assert_eq!(compare_versions("1.80.0", "1.81"), Ordering::Less);
+    }
+
+    #[test]
+    fn missing_parts_count_as_zero() {
+        assert_eq!(compare_versions("20", "20.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("20.0.1", "20"), Ordering::Greater);
+    }
+
+    #[test]
+    fn leading_v_is_ignored() {
+        assert_eq!(compare_versions("v20.11.1", "20.11.1"), Ordering::Equal);
+        assert_eq!(compare_versions("v3", "v2.9"), Ordering::Greater);
+    }
+}
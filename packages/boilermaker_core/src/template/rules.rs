@@ -0,0 +1,197 @@
+use std::path::{Path, PathBuf};
+
+use color_eyre::{Result, eyre::eyre};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use minijinja::{Environment as JinjaEnv, value::Value as JinjaValue};
+
+use crate::{
+    config::{TemplateConfig, TemplateConfigFile, get_template_config},
+    constants::{BOILIGNORE_FILE, COMMON_DIR},
+    template::syntax::TemplateSyntax,
+};
+
+/// Compiled `[render]` rules plus the template's `.boilignore`, `[[files]]` conditions and
+/// `[syntax]`.
+///
+/// `[render]` globs match `/`-separated paths relative to the language dir (see
+/// `template_name`). `.boilignore` uses gitignore syntax and, like a `.gitignore`, matches paths
+/// relative to the template root.
+#[derive(Debug, Clone)]
+pub struct RenderRules {
+    root: PathBuf,
+    lang: String,
+    exclude: GlobSet,
+    copy_only: GlobSet,
+    raw: GlobSet,
+    ignore: Gitignore,
+    files: Vec<TemplateConfigFile>,
+    syntax: TemplateSyntax,
+}
+
+impl RenderRules {
+    #[tracing::instrument]
+    pub fn new(root: &Path, lang: &str, tpl_cnf: &TemplateConfig) -> Result<Self> {
+        let cnf = tpl_cnf.render.clone().unwrap_or_default();
+
+        Ok(RenderRules {
+            root: root.to_path_buf(),
+            lang: lang.to_owned(),
+            exclude: build_glob_set("exclude", &cnf.exclude)?,
+            copy_only: build_glob_set("copy_only", &cnf.copy_only)?,
+            raw: build_glob_set("raw", &cnf.raw)?,
+            ignore: load_boilignore(root)?,
+            files: tpl_cnf.files.clone().unwrap_or_default(),
+            syntax: TemplateSyntax::new(tpl_cnf.syntax.as_ref())?,
+        })
+    }
+
+    /// Like `new`, reading the `boilermaker.toml` in `root`.
+    #[tracing::instrument]
+    pub fn load(root: &Path, lang: &str) -> Result<Self> {
+        Self::new(root, lang, &get_template_config(root)?)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The template's `_common` dir, if it has one.
+    pub fn common_dir(&self) -> Option<PathBuf> {
+        Some(self.root.join(COMMON_DIR)).filter(|dir| dir.is_dir())
+    }
+
+    pub fn syntax(&self) -> &TemplateSyntax {
+        &self.syntax
+    }
+
+    /// Whether the file at `name` is left out of generated projects. `.boilignore` is checked
+    /// against the file's path in the dir it came from: the language dir, or `_common` when the
+    /// language doesn't have it.
+    pub fn is_excluded(&self, name: &str) -> bool {
+        self.is_excluded_in(self.source_dir(name), name)
+    }
+
+    /// Whether the file at `name` should be copied as-is instead of rendered.
+    pub fn is_copy_only(&self, name: &str) -> bool {
+        self.copy_only.is_match(name)
+    }
+
+    /// Whether the file at `name` should be written out verbatim instead of rendered.
+    pub fn is_raw(&self, name: &str) -> bool {
+        self.raw.is_match(name)
+    }
+
+    /// Like `is_excluded`, for a path relative to the template root. Files in `_common` are
+    /// checked like the language's own. Other files outside the language dir (e.g. CI config next
+    /// to `boilermaker.toml`) are only checked against `.boilignore`.
+    pub fn is_excluded_path(&self, path: &str) -> bool {
+        match self.lang_name(path) {
+            Some((dir, name)) => self.is_excluded_in(dir, name),
+            None => self.is_ignored(path),
+        }
+    }
+
+    /// Whether the file at `path`, relative to the template root, belongs in the search index.
+    pub fn is_indexed(&self, path: &str) -> bool {
+        match self.lang_name(path) {
+            Some((dir, name)) => !self.is_excluded_in(dir, name) && !self.is_copy_only(name),
+            None => !self.is_ignored(path),
+        }
+    }
+
+    /// `[[files]]` paths whose `when` expression is false for `ctx`. Every problem is reported
+    /// together.
+    #[tracing::instrument(skip(jinja))]
+    pub fn disabled_paths(&self, jinja: &JinjaEnv, ctx: &JinjaValue) -> Result<Vec<String>> {
+        let mut disabled: Vec<String> = Vec::new();
+        let mut errors: Vec<String> = Vec::new();
+
+        for file in &self.files {
+            let path = file.path.trim_start_matches("./").trim_end_matches('/');
+            let enabled = jinja
+                .compile_expression(&file.when)
+                .and_then(|expr| expr.eval(ctx));
+            match enabled {
+                Ok(v) if !v.is_true() => disabled.push(path.to_owned()),
+                Ok(_) => {}
+                Err(e) => errors.push(format!("{path} (when = {:?}): {e}", file.when)),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(eyre!(
+                "💥 Invalid [[files]] conditions:\n  - {}",
+                errors.join("\n  - ")
+            ));
+        }
+
+        Ok(disabled)
+    }
+
+    /// The dir (language or `_common`) a template root path is in, and the path within it.
+    fn lang_name<'a, 'b>(&'a self, path: &'b str) -> Option<(&'a str, &'b str)> {
+        [self.lang.as_str(), COMMON_DIR]
+            .into_iter()
+            .find_map(|dir| Some((dir, path.strip_prefix(dir)?.strip_prefix('/')?)))
+    }
+
+    /// The dir a work dir file was copied from. `_common` files are only copied when the language
+    /// doesn't have the same path.
+    fn source_dir(&self, name: &str) -> &str {
+        let in_lang = self.root.join(&self.lang).join(name).exists();
+        if !in_lang && self.root.join(COMMON_DIR).join(name).exists() {
+            COMMON_DIR
+        } else {
+            &self.lang
+        }
+    }
+
+    fn is_excluded_in(&self, dir: &str, name: &str) -> bool {
+        self.exclude.is_match(name) || self.is_ignored(&format!("{dir}/{name}"))
+    }
+
+    fn is_ignored(&self, path: &str) -> bool {
+        self.ignore
+            .matched_path_or_any_parents(path, false)
+            .is_ignore()
+    }
+}
+
+/// Whether `name` is one of `paths` or inside one of them.
+pub fn is_at_or_under(name: &str, paths: &[String]) -> bool {
+    paths.iter().any(|p| {
+        name == p
+            || name
+                .strip_prefix(p.as_str())
+                .is_some_and(|rest| rest.starts_with('/'))
+    })
+}
+
+/// Globs where `*` stays within one path segment and `**` crosses directories, like gitignore.
+fn build_glob_set(key: &str, patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| eyre!("💥 Invalid glob in [render] {key}: {pattern:?}: {e}"))?;
+        builder.add(glob);
+    }
+    Ok(builder.build()?)
+}
+
+fn load_boilignore(root: &Path) -> Result<Gitignore> {
+    let path = root.join(BOILIGNORE_FILE);
+    if !path.is_file() {
+        return Ok(Gitignore::empty());
+    }
+
+    let mut builder = GitignoreBuilder::new(root);
+    if let Some(e) = builder.add(&path) {
+        return Err(eyre!("💥 Failed to read {}: {e}", path.display()));
+    }
+    builder
+        .build()
+        .map_err(|e| eyre!("💥 Invalid {}: {e}", path.display()))
+}
@@ -0,0 +1,272 @@
+use std::collections::{BTreeMap, HashMap};
+
+use color_eyre::{Result, eyre::eyre};
+use minijinja::value::{Value as JinjaValue, ValueKind};
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::config::{TemplateConfigVarSchema, TemplateConfigVarType as VarType};
+
+/// Coerce and validate every variable declared in `[schema]` against the merged context.
+///
+/// Values coming from the command line or prompts are strings, so they're converted to the
+/// declared type first. All problems are collected and reported together.
+#[tracing::instrument]
+pub fn apply_var_schema(
+    ctx: JinjaValue,
+    schema: &HashMap<String, TemplateConfigVarSchema>,
+) -> Result<JinjaValue> {
+    let mut vars = BTreeMap::<String, JinjaValue>::deserialize(&ctx)?;
+    let mut errors: Vec<String> = Vec::new();
+
+    let mut names = schema.keys().collect::<Vec<_>>();
+    names.sort();
+
+    for name in names {
+        let var_schema = &schema[name];
+        let value = vars.get(name).cloned().unwrap_or(JinjaValue::UNDEFINED);
+
+        match coerce_var(&value, var_schema) {
+            Ok(coerced) => {
+                if let Err(e) = validate_var(&coerced, var_schema) {
+                    errors.push(format!("{name}: {e}"));
+                } else if !coerced.is_undefined() {
+                    vars.insert(name.to_owned(), coerced);
+                }
+            }
+            Err(e) => errors.push(format!("{name}: {e}")),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(eyre!(
+            "💥 Invalid template variables:\n  - {}",
+            errors.join("\n  - ")
+        ));
+    }
+
+    Ok(JinjaValue::from_serialize(vars))
+}
+
+/// Convert `value` to the declared type. Undefined/none values are passed through untouched.
+pub fn coerce_var(value: &JinjaValue, schema: &TemplateConfigVarSchema) -> Result<JinjaValue> {
+    if value.is_undefined() || value.is_none() {
+        return Ok(value.clone());
+    }
+
+    let invalid = || eyre!("expected {}, got {:?}", schema.var_type, value.to_string());
+
+    match (schema.var_type, value.kind()) {
+        (VarType::String, ValueKind::String) => Ok(value.clone()),
+        (VarType::String, ValueKind::Number | ValueKind::Bool) => {
+            Ok(JinjaValue::from(value.to_string()))
+        }
+        (VarType::Int, ValueKind::Number) if value.is_integer() => Ok(value.clone()),
+        (VarType::Int, ValueKind::String) => value
+            .as_str()
+            .and_then(|s| s.trim().parse::<i64>().ok())
+            .map(JinjaValue::from)
+            .ok_or_else(invalid),
+        (VarType::Float, ValueKind::Number) => f64::try_from(value.clone())
+            .map(JinjaValue::from)
+            .map_err(|_| invalid()),
+        (VarType::Float, ValueKind::String) => value
+            .as_str()
+            .and_then(|s| s.trim().parse::<f64>().ok())
+            .map(JinjaValue::from)
+            .ok_or_else(invalid),
+        (VarType::Bool, ValueKind::Bool) => Ok(value.clone()),
+        (VarType::Bool, ValueKind::String) => value
+            .as_str()
+            .and_then(parse_bool)
+            .map(JinjaValue::from)
+            .ok_or_else(invalid),
+        (VarType::List, ValueKind::Seq) => Ok(value.clone()),
+        (VarType::List, ValueKind::String) => Ok(JinjaValue::from(split_list(
+            value.as_str().unwrap_or_default(),
+        ))),
+        // Choices can be numbers or bools, so string input is read as the choices' kind.
+        (VarType::Enum, ValueKind::String) => Ok(schema
+            .choices
+            .as_ref()
+            .and_then(|choices| choices.first())
+            .zip(value.as_str())
+            .and_then(|(first, s)| coerce_to_kind(s.trim(), first))
+            .unwrap_or_else(|| value.clone())),
+        (VarType::Enum, ValueKind::Number | ValueKind::Bool) => Ok(value.clone()),
+        _ => Err(invalid()),
+    }
+}
+
+/// Check `required`, `choices` and `pattern` against an already coerced value.
+pub fn validate_var(value: &JinjaValue, schema: &TemplateConfigVarSchema) -> Result<()> {
+    let is_empty = value.is_undefined()
+        || value.is_none()
+        || value.as_str().is_some_and(|s| s.trim().is_empty());
+
+    if is_empty {
+        return if schema.required {
+            Err(eyre!("a value is required"))
+        } else {
+            Ok(())
+        };
+    }
+
+    if schema.var_type == VarType::Enum && schema.choices.is_none() {
+        return Err(eyre!("type `enum` requires `choices` in boilermaker.toml"));
+    }
+
+    let items = match value.kind() {
+        ValueKind::Seq => value.try_iter()?.collect(),
+        _ => vec![value.clone()],
+    };
+
+    if let Some(choices) = &schema.choices {
+        for item in &items {
+            if !choices.contains(item) {
+                return Err(eyre!(
+                    "{:?} is not one of: {}",
+                    item.to_string(),
+                    format_choices(choices)
+                ));
+            }
+        }
+    }
+
+    if let Some(pattern) = &schema.pattern {
+        let re = Regex::new(pattern).map_err(|e| eyre!("invalid pattern {pattern:?}: {e}"))?;
+        for item in &items {
+            if !re.is_match(&item.to_string()) {
+                return Err(eyre!(
+                    "{:?} does not match pattern {pattern:?}",
+                    item.to_string()
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Convert string leaves in `overrides` to the kind (bool/number/list) of the value at the same
+/// path in `defaults`, so `--var port=9000` stays a number when `boilermaker.toml` says
+/// `port = 8080`, and `--var features=a,b` is a list when it says `features = ["a"]`.
+///
+/// Top-level variables with a `[schema]` entry are left alone; the schema decides their type.
+#[tracing::instrument]
+pub fn coerce_to_defaults(
+    overrides: JinjaValue,
+    defaults: &JinjaValue,
+    schema: Option<&HashMap<String, TemplateConfigVarSchema>>,
+) -> Result<JinjaValue> {
+    let mut errors: Vec<String> = Vec::new();
+    let mut vars = BTreeMap::<String, JinjaValue>::deserialize(&overrides)?;
+
+    for (name, value) in vars.iter_mut() {
+        if schema.is_some_and(|s| s.contains_key(name)) {
+            continue;
+        }
+        let default = defaults.get_attr(name).unwrap_or_default();
+        *value = coerce_leaves(value.clone(), &default, name, &mut errors);
+    }
+
+    if !errors.is_empty() {
+        return Err(eyre!(
+            "💥 Invalid template variables:\n  - {}",
+            errors.join("\n  - ")
+        ));
+    }
+
+    Ok(JinjaValue::from_serialize(vars))
+}
+
+fn coerce_leaves(
+    value: JinjaValue,
+    default: &JinjaValue,
+    path: &str,
+    errors: &mut Vec<String>,
+) -> JinjaValue {
+    match value.kind() {
+        ValueKind::Map => {
+            let mut vars = BTreeMap::<String, JinjaValue>::deserialize(&value).unwrap_or_default();
+            for (name, child) in vars.iter_mut() {
+                let child_default = default.get_attr(name).unwrap_or_default();
+                let child_path = format!("{path}.{name}");
+                *child = coerce_leaves(child.clone(), &child_default, &child_path, errors);
+            }
+            JinjaValue::from_serialize(vars)
+        }
+        ValueKind::String if matches!(default.kind(), ValueKind::Bool | ValueKind::Number) => {
+            let input = value.as_str().unwrap_or_default();
+            match coerce_to_kind(input, default) {
+                Some(coerced) => coerced,
+                None => {
+                    errors.push(format!(
+                        "{path}: expected {} like the default, got {input:?}",
+                        default.kind()
+                    ));
+                    value
+                }
+            }
+        }
+        ValueKind::String if default.kind() == ValueKind::Seq => {
+            let input = value.as_str().unwrap_or_default();
+            let first = default.get_item_by_index(0).unwrap_or_default();
+            let items = split_list(input)
+                .iter()
+                .map(|item| coerce_to_kind(item, &first))
+                .collect::<Option<Vec<_>>>();
+            match items {
+                Some(items) => JinjaValue::from(items),
+                None => {
+                    errors.push(format!(
+                        "{path}: expected a list of {} like the default, got {input:?}",
+                        first.kind()
+                    ));
+                    value
+                }
+            }
+        }
+        _ => value,
+    }
+}
+
+/// Split comma-separated list input, e.g. `auth, grpc` → `["auth", "grpc"]`.
+fn split_list(input: &str) -> Vec<String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return vec![];
+    }
+    input
+        .split(',')
+        .map(|item| item.trim().to_owned())
+        .collect()
+}
+
+/// Parse `input` into the same kind of value as `default` (bool, int, float or string).
+pub fn coerce_to_kind(input: &str, default: &JinjaValue) -> Option<JinjaValue> {
+    match default.kind() {
+        ValueKind::Bool => parse_bool(input).map(JinjaValue::from),
+        ValueKind::Number if default.is_integer() => {
+            input.parse::<i64>().ok().map(JinjaValue::from)
+        }
+        ValueKind::Number => input.parse::<f64>().ok().map(JinjaValue::from),
+        _ => Some(JinjaValue::from(input)),
+    }
+}
+
+pub fn format_choices(choices: &[JinjaValue]) -> String {
+    choices
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn parse_bool(s: &str) -> Option<bool> {
+    match s.trim().to_lowercase().as_str() {
+        "true" | "yes" | "y" | "1" => Some(true),
+        "false" | "no" | "n" | "0" => Some(false),
+        _ => None,
+    }
+}
@@ -1,11 +1,19 @@
 use color_eyre::eyre::{Result, eyre};
+use minijinja::value::Value as JinjaValue;
 use regex::Regex;
-use std::collections::HashSet;
-use std::fs::{File, read_to_string};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
 use std::io::Read as _;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+use crate::template::{
+    PathMarker, RenderEnv,
+    context::is_defined_path,
+    paths::{loop_vars, path_markers},
+    template_name,
+};
+
 // An alpha-numeric string enclosed in {{ }}.
 // TODO: re-strengthen this regex to whatever Jinja uses internally
 const JINJA_VAR_REGEX: &str = r"\{\{\s*([\w_-]+)\s*\}\}";
@@ -57,20 +65,110 @@ fn find_vars_in_file(re: &Regex, path: &Path) -> Result<Vec<String>> {
     Ok(vars)
 }
 
+/// Find the variables each template in `paths` uses (as dot paths, e.g. `config.db.host`),
+/// parsing each one with the `env` for its syntax. Keyed by template name relative to `root`.
 #[tracing::instrument]
-pub fn get_minijinja_vars(paths: &Vec<PathBuf>) -> Result<HashSet<String>> {
-    let mut vars: HashSet<String> = HashSet::new();
-    let mut jinja = minijinja::Environment::new();
+pub fn get_minijinja_vars(
+    root: &Path,
+    paths: &[PathBuf],
+    env: &RenderEnv,
+) -> Result<BTreeMap<String, HashSet<String>>> {
+    let mut vars: BTreeMap<String, HashSet<String>> = BTreeMap::new();
 
     for path in paths {
-        let tpl_name = path.file_name().unwrap().to_str().unwrap();
-        let tpl_source = read_to_string(path)?;
+        let tpl_name = template_name(root, path)?;
+        let t = env.env_for(&tpl_name).get_template(&tpl_name)?;
+        vars.insert(tpl_name, t.undeclared_variables(true));
+    }
+
+    Ok(vars)
+}
 
-        jinja.add_template_owned(tpl_name, tpl_source)?;
-        let t = jinja.get_template(tpl_name)?;
+/// Find the variables used in `___x___`/`---x---` file path segments under `root`. Keyed by
+/// path relative to `root`, with a trailing `/` for directories. A loop marker's own variable
+/// isn't reported.
+#[tracing::instrument(skip(jinja))]
+pub fn get_filepath_vars(
+    root: &Path,
+    jinja: &minijinja::Environment,
+) -> Result<BTreeMap<String, HashSet<String>>> {
+    let mut vars: BTreeMap<String, HashSet<String>> = BTreeMap::new();
+
+    for entry in WalkDir::new(root).min_depth(1) {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy();
+
+        let mut path_vars: HashSet<String> = HashSet::new();
+        for marker in path_markers(&file_name) {
+            match marker {
+                PathMarker::Expr { expr, .. } => {
+                    path_vars.extend(jinja.compile_expression(expr)?.undeclared_variables(true));
+                }
+                PathMarker::Loop {
+                    expr, var, list, ..
+                } => {
+                    path_vars.extend(jinja.compile_expression(list)?.undeclared_variables(true));
+                    path_vars.extend(
+                        jinja
+                            .compile_expression(expr)?
+                            .undeclared_variables(true)
+                            .into_iter()
+                            .filter(|v| v.split('.').next() != Some(var)),
+                    );
+                }
+            }
+        }
 
-        vars.extend(t.undeclared_variables(true));
+        if !path_vars.is_empty() {
+            let mut name = template_name(root, entry.path())?;
+            if entry.file_type().is_dir() {
+                name.push('/');
+            }
+            vars.insert(name, path_vars);
+        }
     }
 
     Ok(vars)
 }
+
+/// Compare the variables referenced by `paths` (file contents) and by file paths under `root`
+/// against `ctx`. Returns the references that don't resolve, keyed by file.
+///
+/// Built-in functions (`range`, `uuid`, ...) aren't variables and are skipped, as are the
+/// variables set by loop markers in the paths above a file.
+#[tracing::instrument]
+pub fn find_undefined_vars(
+    root: &Path,
+    paths: &[PathBuf],
+    env: &RenderEnv,
+    ctx: &JinjaValue,
+) -> Result<BTreeMap<String, Vec<String>>> {
+    let globals = env
+        .default_env()
+        .globals()
+        .map(|(name, _)| name.to_owned())
+        .collect::<HashSet<_>>();
+
+    let mut refs = get_minijinja_vars(root, paths, env)?;
+    for (name, vars) in get_filepath_vars(root, env.default_env())? {
+        refs.entry(name).or_default().extend(vars);
+    }
+
+    let mut undefined: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (name, vars) in refs {
+        let bound = loop_vars(&name);
+        let mut missing = vars
+            .into_iter()
+            .filter(|var| {
+                let top = var.split('.').next().unwrap_or_default();
+                !globals.contains(top) && !bound.contains(top) && !is_defined_path(ctx, var)
+            })
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            missing.sort();
+            undefined.insert(name, missing);
+        }
+    }
+
+    Ok(undefined)
+}
@@ -0,0 +1,96 @@
+use color_eyre::{Result, eyre::eyre};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use minijinja::syntax::SyntaxConfig;
+
+use crate::config::{TemplateConfigDelimiters, TemplateConfigSyntax};
+
+/// Jinja syntax for a template: the `[syntax]` delimiters plus `[[syntax.overrides]]` for files
+/// matching certain globs (e.g. GitHub Actions workflows, which use `${{ }}` themselves).
+///
+/// minijinja sets the syntax per environment, so callers keep one environment per entry in
+/// `configs()` and pick one with `index_for`.
+#[derive(Debug, Clone)]
+pub struct TemplateSyntax {
+    configs: Vec<SyntaxConfig>,
+    overrides: Vec<GlobSet>,
+}
+
+impl Default for TemplateSyntax {
+    fn default() -> Self {
+        TemplateSyntax {
+            configs: vec![SyntaxConfig::default()],
+            overrides: vec![],
+        }
+    }
+}
+
+impl TemplateSyntax {
+    #[tracing::instrument]
+    pub fn new(cnf: Option<&TemplateConfigSyntax>) -> Result<Self> {
+        let Some(cnf) = cnf else {
+            return Ok(TemplateSyntax::default());
+        };
+
+        let mut configs = vec![build_syntax(&[&cnf.delimiters])?];
+        let mut overrides = Vec::with_capacity(cnf.overrides.len());
+        for o in &cnf.overrides {
+            let mut builder = GlobSetBuilder::new();
+            for pattern in &o.paths {
+                let glob = GlobBuilder::new(pattern)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|e| {
+                        eyre!("💥 Invalid glob in [[syntax.overrides]] paths: {pattern:?}: {e}")
+                    })?;
+                builder.add(glob);
+            }
+            overrides.push(builder.build()?);
+            configs.push(build_syntax(&[&cnf.delimiters, &o.delimiters])?);
+        }
+
+        Ok(TemplateSyntax { configs, overrides })
+    }
+
+    /// All syntax configs. The first one is the template's default.
+    pub fn configs(&self) -> &[SyntaxConfig] {
+        &self.configs
+    }
+
+    /// Index into `configs()` for the file at `name`. The first matching override wins.
+    pub fn index_for(&self, name: &str) -> usize {
+        self.overrides
+            .iter()
+            .position(|globs| globs.is_match(name))
+            .map_or(0, |i| i + 1)
+    }
+
+    /// The syntax config for the file at `name`.
+    pub fn config_for(&self, name: &str) -> &SyntaxConfig {
+        &self.configs[self.index_for(name)]
+    }
+
+    /// The template's default syntax config.
+    pub fn default_config(&self) -> &SyntaxConfig {
+        &self.configs[0]
+    }
+}
+
+/// Build a syntax config from delimiter layers, later layers winning. Anything not set anywhere
+/// keeps the Jinja default.
+fn build_syntax(layers: &[&TemplateConfigDelimiters]) -> Result<SyntaxConfig> {
+    let mut builder = SyntaxConfig::builder();
+    for layer in layers {
+        if let Some([start, end]) = &layer.block {
+            builder.block_delimiters(start.clone(), end.clone());
+        }
+        if let Some([start, end]) = &layer.variable {
+            builder.variable_delimiters(start.clone(), end.clone());
+        }
+        if let Some([start, end]) = &layer.comment {
+            builder.comment_delimiters(start.clone(), end.clone());
+        }
+    }
+    builder
+        .build()
+        .map_err(|e| eyre!("💥 Invalid [syntax] delimiters: {e}"))
+}
@@ -0,0 +1,161 @@
+use std::{collections::BTreeSet, fs, path::Path};
+
+use color_eyre::{Result, eyre::eyre};
+use git2::{IndexEntry, IndexTime, MergeFileOptions, Repository};
+use walkdir::WalkDir;
+
+use crate::{
+    template::{apply::ApplyEntry, template_name},
+    util::file::{bytes_to_text, copy_file, side_file_path},
+};
+
+/// Three-way merge the template's changes into `project_dir`.
+///
+/// `base_dir` is the old template version rendered with the project's answers and `theirs_dir`
+/// the new one. Only files the template changed are touched: the project's own edits are kept
+/// and conflicting hunks get `<<<<<<<`/`>>>>>>>` markers. With `write` false, nothing is written
+/// and the report says what would happen.
+#[tracing::instrument]
+pub fn merge_project(
+    base_dir: &Path,
+    theirs_dir: &Path,
+    project_dir: &Path,
+    merge_dir: &Path,
+    write: bool,
+) -> Result<Vec<ApplyEntry>> {
+    let mut paths: BTreeSet<String> = BTreeSet::new();
+    for dir in [base_dir, theirs_dir] {
+        for entry in WalkDir::new(dir).min_depth(1) {
+            let entry = entry?;
+            if entry.file_type().is_file() {
+                paths.insert(template_name(dir, entry.path())?);
+            }
+        }
+    }
+
+    let merger =
+        Repository::init_bare(merge_dir).map_err(|e| eyre!("💥 Failed to set up merge: {e}"))?;
+    let mut report: Vec<ApplyEntry> = Vec::new();
+
+    for path in paths {
+        let base = read_opt(&base_dir.join(&path))?;
+        let theirs = read_opt(&theirs_dir.join(&path))?;
+        if base == theirs {
+            continue;
+        }
+        let dest = project_dir.join(&path);
+        let ours = read_opt(&dest)?;
+
+        let result = match (base, theirs, ours) {
+            (None, None, _) | (Some(_), None, None) => continue,
+            (None, Some(_), None) => {
+                if write {
+                    copy_file(&theirs_dir.join(&path), &dest)?;
+                }
+                "created".to_owned()
+            }
+            (Some(base), None, Some(ours)) => {
+                if ours == base {
+                    if write {
+                        fs::remove_file(&dest)?;
+                    }
+                    "deleted".to_owned()
+                } else {
+                    "kept (removed from template, changed in project)".to_owned()
+                }
+            }
+            (Some(_), Some(_), None) => "skipped (deleted in project)".to_owned(),
+            (base, Some(theirs), Some(ours)) => {
+                if ours == theirs {
+                    "unchanged".to_owned()
+                } else if base.as_ref() == Some(&ours) {
+                    if write {
+                        copy_file(&theirs_dir.join(&path), &dest)?;
+                    }
+                    "updated".to_owned()
+                } else if base.is_none() {
+                    // Added by both with no common version to merge from.
+                    if write {
+                        copy_file(&theirs_dir.join(&path), &side_file_path(&dest, "new"))?;
+                    }
+                    format!("kept, wrote {path}.new (added by template and project)")
+                } else if [base.as_ref(), Some(&ours), Some(&theirs)]
+                    .into_iter()
+                    .flatten()
+                    .any(|b| !is_mergeable(b))
+                {
+                    if write {
+                        copy_file(&theirs_dir.join(&path), &side_file_path(&dest, "new"))?;
+                    }
+                    format!("kept, wrote {path}.new (binary or too large, changed in project)")
+                } else {
+                    let base = base.unwrap_or_default();
+                    let (merged, clean) = merge_file(&merger, &path, &base, &ours, &theirs)?;
+                    if write {
+                        fs::write(&dest, merged)?;
+                    }
+                    if clean { "merged" } else { "conflict" }.to_owned()
+                }
+            }
+        };
+
+        report.push(ApplyEntry { result, path });
+    }
+
+    Ok(report)
+}
+
+/// Merge one file's changes, returning the merged content and whether it merged cleanly.
+fn merge_file(
+    merger: &Repository,
+    path: &str,
+    base: &[u8],
+    ours: &[u8],
+    theirs: &[u8],
+) -> Result<(Vec<u8>, bool)> {
+    let entry = |content: &[u8]| -> Result<IndexEntry> {
+        Ok(IndexEntry {
+            ctime: IndexTime::new(0, 0),
+            mtime: IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: 0o100644,
+            uid: 0,
+            gid: 0,
+            file_size: u32::try_from(content.len())
+                .map_err(|_| eyre!("💥 {path} is too large to merge."))?,
+            id: merger.blob(content)?,
+            flags: 0,
+            flags_extended: 0,
+            path: path.as_bytes().to_vec(),
+        })
+    };
+
+    let mut opts = MergeFileOptions::new();
+    opts.ancestor_label("template (old)")
+        .our_label("project")
+        .their_label("template (new)");
+    let result = merger
+        .merge_file_from_index(
+            &entry(base)?,
+            &entry(ours)?,
+            &entry(theirs)?,
+            Some(&mut opts),
+        )
+        .map_err(|e| eyre!("💥 Failed to merge {path}: {e}"))?;
+
+    Ok((result.content().to_vec(), result.is_automergeable()))
+}
+
+/// Whether `content` can be merged line by line: text that fits in a git index entry.
+fn is_mergeable(content: &[u8]) -> bool {
+    u32::try_from(content.len()).is_ok() && bytes_to_text(content).is_some()
+}
+
+fn read_opt(path: &Path) -> Result<Option<Vec<u8>>> {
+    Ok(if path.is_file() {
+        Some(fs::read(path)?)
+    } else {
+        None
+    })
+}
@@ -0,0 +1,157 @@
+use std::{
+    fmt, fs,
+    io::{self, Cursor, Write},
+    path::Path,
+};
+
+use chrono::{Datelike, Local, Timelike};
+use clap::ValueEnum;
+use color_eyre::{Result, eyre::eyre};
+use flate2::{Compression, write::GzEncoder};
+use walkdir::WalkDir;
+use zip::{CompressionMethod, DateTime as ZipDateTime, ZipWriter, write::SimpleFileOptions};
+
+use crate::template::template_name;
+
+/// How `boil new` writes the generated project.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// A project directory.
+    Dir,
+    /// A tar archive.
+    Tar,
+    /// A gzipped tar archive.
+    #[value(name = "tar.gz")]
+    TarGz,
+    /// A zip archive.
+    Zip,
+}
+
+impl OutputFormat {
+    /// The archive format for a file name, by its extension.
+    pub fn from_path(path: &str) -> Option<Self> {
+        if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+            Some(OutputFormat::TarGz)
+        } else if path.ends_with(".tar") {
+            Some(OutputFormat::Tar)
+        } else if path.ends_with(".zip") {
+            Some(OutputFormat::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self
+            .to_possible_value()
+            .map(|v| v.get_name().to_owned())
+            .unwrap_or_default();
+        write!(f, "{name}")
+    }
+}
+
+/// Write everything in `src_dir` to `out` as a `format` archive, under a top-level `root` dir.
+///
+/// File modes and symlinks are kept. Zip archives are built in memory first, since the format
+/// needs to seek and `out` may be a pipe.
+#[tracing::instrument(skip(out))]
+pub fn write_archive(
+    src_dir: &Path,
+    root: &str,
+    format: OutputFormat,
+    mut out: impl Write,
+) -> Result<()> {
+    match format {
+        OutputFormat::Dir => return Err(eyre!("💥 `dir` isn't an archive format.")),
+        OutputFormat::Tar => {
+            write_tar(src_dir, root, &mut out)?;
+        }
+        OutputFormat::TarGz => {
+            let mut gz = GzEncoder::new(&mut out, Compression::default());
+            write_tar(src_dir, root, &mut gz)?;
+            gz.finish()?;
+        }
+        OutputFormat::Zip => {
+            let mut buf = Cursor::new(Vec::new());
+            write_zip(src_dir, root, &mut buf)?;
+            out.write_all(buf.get_ref())?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn write_tar(src_dir: &Path, root: &str, out: impl Write) -> Result<()> {
+    let mut tar = tar::Builder::new(out);
+    tar.follow_symlinks(false);
+    tar.append_dir_all(root, src_dir)
+        .map_err(|e| eyre!("💥 Failed to write tar archive: {e}"))?;
+    tar.into_inner()?;
+    Ok(())
+}
+
+fn write_zip(src_dir: &Path, root: &str, out: &mut Cursor<Vec<u8>>) -> Result<()> {
+    let mut zip = ZipWriter::new(out);
+
+    for entry in WalkDir::new(src_dir).sort_by_file_name() {
+        let entry = entry?;
+        let meta = entry.path().symlink_metadata()?;
+        let rel = template_name(src_dir, entry.path())?;
+        let name = if rel.is_empty() {
+            root.to_owned()
+        } else {
+            format!("{root}/{rel}")
+        };
+        let mut opts = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .unix_permissions(file_mode(&meta))
+            .large_file(meta.len() >= u32::MAX as u64);
+        if let Some(time) = zip_time(&meta) {
+            opts = opts.last_modified_time(time);
+        }
+
+        if meta.file_type().is_symlink() {
+            let target = fs::read_link(entry.path())?;
+            zip.add_symlink(name, target.to_string_lossy(), opts)?;
+        } else if meta.is_dir() {
+            zip.add_directory(format!("{name}/"), opts)?;
+        } else {
+            zip.start_file(name, opts)?;
+            io::copy(&mut fs::File::open(entry.path())?, &mut zip)?;
+        }
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+/// `meta`'s modification time in local time, as zip stores it. `None` if it's out of zip's range.
+fn zip_time(meta: &fs::Metadata) -> Option<ZipDateTime> {
+    let time: chrono::DateTime<Local> = meta.modified().ok()?.into();
+    ZipDateTime::from_date_and_time(
+        u16::try_from(time.year()).ok()?,
+        time.month() as u8,
+        time.day() as u8,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+    )
+    .ok()
+}
+
+#[cfg(unix)]
+fn file_mode(meta: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode()
+}
+
+#[cfg(not(unix))]
+fn file_mode(meta: &fs::Metadata) -> u32 {
+    match (meta.is_dir(), meta.permissions().readonly()) {
+        (true, _) => 0o755,
+        (false, true) => 0o444,
+        (false, false) => 0o644,
+    }
+}
@@ -1,4 +1,7 @@
-use std::{env, fs, path::PathBuf};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
 
 use color_eyre::{Result, eyre::eyre};
 use fs_extra::{copy_items, dir::CopyOptions};
@@ -11,6 +14,74 @@ pub fn read_file_to_string(path: &PathBuf) -> Result<String> {
     Ok(content)
 }
 
+/// Read `path` as text. Returns `None` for binary files (containing NUL bytes) and files that
+/// aren't valid UTF-8, which should be copied byte-for-byte instead of rendered or indexed.
+#[tracing::instrument]
+pub fn read_text_file(path: &Path) -> Result<Option<String>> {
+    let bytes = fs::read(path)?;
+    Ok(bytes_to_text(&bytes).map(str::to_owned))
+}
+
+/// Like `read_text_file`, for content that's already been read.
+pub fn bytes_to_text(bytes: &[u8]) -> Option<&str> {
+    if bytes.contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(bytes).ok()
+}
+
+/// Copy the file at `src` to `dest`, creating `dest`'s parent dirs.
+#[tracing::instrument]
+pub fn copy_file(src: &Path, dest: &Path) -> Result<()> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(src, dest).map_err(|e| eyre!("💥 Failed to write {}: {e}", dest.display()))?;
+    Ok(())
+}
+
+/// Copy the file or directory tree at `src` to `dest`, creating `dest`'s parent dirs.
+#[tracing::instrument]
+pub fn copy_path(src: &Path, dest: &Path) -> Result<()> {
+    for entry in WalkDir::new(src) {
+        let entry = entry?;
+        let rel = entry.path().strip_prefix(src)?;
+        let target = if rel.as_os_str().is_empty() {
+            dest.to_path_buf()
+        } else {
+            dest.join(rel)
+        };
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            copy_file(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Copy the files in the `src` tree that `dest` doesn't already have, creating dirs as needed.
+#[tracing::instrument]
+pub fn copy_missing(src: &Path, dest: &Path) -> Result<()> {
+    for entry in WalkDir::new(src).min_depth(1) {
+        let entry = entry?;
+        let target = dest.join(entry.path().strip_prefix(src)?);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else if !target.exists() {
+            copy_file(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// `path` with `.ext` appended, e.g. `ci.yml` → `ci.yml.rej`.
+pub fn side_file_path(path: &Path, ext: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{ext}"));
+    PathBuf::from(name)
+}
+
 #[tracing::instrument]
 pub fn remove_git_dir(dir: &PathBuf) -> Result<()> {
     let git_dir = dir.join(".git");
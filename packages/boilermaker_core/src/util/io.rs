@@ -1,6 +1,6 @@
 use std::io;
 
-use color_eyre::Result;
+use color_eyre::{Result, eyre::eyre};
 
 #[tracing::instrument]
 pub fn prompt_confirm(prompt: &str, correct_phrase: &str) -> Result<bool> {
@@ -12,3 +12,25 @@ pub fn prompt_confirm(prompt: &str, correct_phrase: &str) -> Result<bool> {
 
     Ok(confirmation.trim() == correct_phrase)
 }
+
+/// Like `prompt_line`, but closed input is an error, so callers that ask again on a bad answer
+/// can't loop forever.
+#[tracing::instrument]
+pub fn prompt_input(prompt: &str) -> Result<String> {
+    prompt_line(prompt)?.ok_or_else(|| eyre!("💥 Input closed before an answer was given."))
+}
+
+/// Print `prompt` and read a trimmed line. `None` when stdin is closed (e.g. Ctrl-D).
+#[tracing::instrument]
+pub fn prompt_line(prompt: &str) -> Result<Option<String>> {
+    print!("{prompt}");
+    io::Write::flush(&mut io::stdout())?;
+
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input)? == 0 {
+        println!();
+        return Ok(None);
+    }
+
+    Ok(Some(input.trim().to_string()))
+}
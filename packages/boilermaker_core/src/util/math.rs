@@ -13,3 +13,13 @@ pub fn rand_i64_between(min: i64, max: i64) -> i64 {
     let mut rng = rand::rng();
     rng.random_range(min..=max)
 }
+
+/// Generates a random alphanumeric string of `len` chars.
+#[tracing::instrument]
+pub fn rand_alphanumeric(len: usize) -> String {
+    rand::rng()
+        .sample_iter(rand::distr::Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
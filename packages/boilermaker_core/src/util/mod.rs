@@ -1,3 +1,4 @@
+pub mod archive;
 pub mod crypto;
 pub mod env;
 pub mod file;
@@ -7,6 +8,7 @@ pub mod io;
 pub mod markdown;
 pub mod math;
 pub mod output;
+pub mod pool;
 pub mod string;
 pub mod time;
 pub mod validation;
@@ -4,13 +4,22 @@ use tracing::error;
 use crate::constants::URL_PREFIX_PATTERN;
 
 pub fn print_table<I, T>(rows: I)
+where
+    I: IntoIterator<Item = T>,
+    T: Tabled,
+{
+    print!("\n{}\n", format_table(rows));
+}
+
+/// Like `print_table`, returning the table instead, e.g. to log it.
+pub fn format_table<I, T>(rows: I) -> String
 where
     I: IntoIterator<Item = T>,
     T: Tabled,
 {
     let mut table = Table::new(rows);
     table.with(Style::psql());
-    print!("\n{table}\n");
+    table.to_string()
 }
 
 pub fn print_table_error<I, T>(rows: I, msg: Option<&str>)
@@ -9,3 +9,156 @@ pub fn string_to_option(s: &str) -> Option<String> {
         Some(s.to_string())
     }
 }
+
+/// Lowercase `s` and join its alphanumeric runs with `-`, e.g. "Hello, World!" → "hello-world".
+pub fn slugify(s: &str) -> String {
+    s.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Words that don't follow the usual English suffix rules.
+const IRREGULAR_PLURALS: &[(&str, &str)] = &[
+    ("child", "children"),
+    ("foot", "feet"),
+    ("goose", "geese"),
+    ("half", "halves"),
+    ("knife", "knives"),
+    ("leaf", "leaves"),
+    ("life", "lives"),
+    ("man", "men"),
+    ("mouse", "mice"),
+    ("person", "people"),
+    ("tooth", "teeth"),
+    ("wife", "wives"),
+    ("wolf", "wolves"),
+    ("woman", "women"),
+];
+
+/// Words that are the same in singular and plural.
+const UNCOUNTABLE: &[&str] = &[
+    "data",
+    "deer",
+    "equipment",
+    "fish",
+    "info",
+    "information",
+    "metadata",
+    "news",
+    "series",
+    "sheep",
+    "species",
+];
+
+/// English plural of the last word in `s`, e.g. "category" → "categories",
+/// "user_address" → "user_addresses", "UserAddress" → "UserAddresses". Words are split at
+/// non-letters and camelCase humps, and everything before the last word is left as written.
+/// Keeps the case of the word (`Person` → `People`, `BOX` → `BOXES`).
+pub fn pluralize(s: &str) -> String {
+    let (prefix, word) = s.split_at(last_word_start(s));
+    if word.is_empty() {
+        return s.to_owned();
+    }
+
+    let lower = word.to_lowercase();
+    let plural = if UNCOUNTABLE.contains(&lower.as_str()) {
+        lower.clone()
+    } else if let Some((_, plural)) = IRREGULAR_PLURALS.iter().find(|(one, _)| *one == lower) {
+        (*plural).to_owned()
+    } else if ["s", "x", "z", "ch", "sh"]
+        .iter()
+        .any(|end| lower.ends_with(end))
+    {
+        format!("{lower}es")
+    } else if let Some(stem) = lower.strip_suffix('y')
+        && !stem.ends_with(['a', 'e', 'i', 'o', 'u'])
+        && !stem.is_empty()
+    {
+        format!("{stem}ies")
+    } else {
+        format!("{lower}s")
+    };
+
+    let plural = if word.chars().all(|c| !c.is_lowercase()) && word.chars().count() > 1 {
+        plural.to_uppercase()
+    } else if word.starts_with(char::is_uppercase) {
+        let mut chars = plural.chars();
+        chars
+            .next()
+            .map(|c| c.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    } else {
+        plural
+    };
+
+    format!("{prefix}{plural}")
+}
+
+/// Byte offset of the last word in `s`: the trailing run of letters, starting at its last
+/// camelCase hump (`userAddress`, `HTTPRequest`).
+fn last_word_start(s: &str) -> usize {
+    let chars = s.char_indices().collect::<Vec<_>>();
+    let mut start = s.len();
+    for (idx, &(i, c)) in chars.iter().enumerate().rev() {
+        if !c.is_alphabetic() {
+            break;
+        }
+        start = i;
+        let Some(&(_, prev)) = idx.checked_sub(1).and_then(|p| chars.get(p)) else {
+            break;
+        };
+        let next_is_lower = chars.get(idx + 1).is_some_and(|(_, n)| n.is_lowercase());
+        if c.is_uppercase() && (prev.is_lowercase() || (prev.is_uppercase() && next_is_lower)) {
+            break;
+        }
+    }
+    start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pluralizes_regular_words() {
+        assert_eq!(pluralize("user"), "users");
+        assert_eq!(pluralize("category"), "categories");
+        assert_eq!(pluralize("day"), "days");
+        assert_eq!(pluralize("box"), "boxes");
+        assert_eq!(pluralize("match"), "matches");
+    }
+
+    #[test]
+    fn pluralizes_irregular_and_uncountable_words() {
+        assert_eq!(pluralize("person"), "people");
+        assert_eq!(pluralize("Person"), "People");
+        assert_eq!(pluralize("sheep"), "sheep");
+        assert_eq!(pluralize("metadata"), "metadata");
+    }
+
+    #[test]
+    fn keeps_the_case_of_the_word() {
+        assert_eq!(pluralize("BOX"), "BOXES");
+        assert_eq!(pluralize("Category"), "Categories");
+    }
+
+    #[test]
+    fn pluralizes_the_last_word_only() {
+        assert_eq!(pluralize("user_address"), "user_addresses");
+        assert_eq!(pluralize("api-key"), "api-keys");
+        assert_eq!(pluralize("USER_BOX"), "USER_BOXES");
+        assert_eq!(pluralize("v2"), "v2");
+        assert_eq!(pluralize(""), "");
+    }
+
+    #[test]
+    fn pluralizes_the_last_camel_case_word() {
+        assert_eq!(pluralize("UserAddress"), "UserAddresses");
+        assert_eq!(pluralize("userAddress"), "userAddresses");
+        assert_eq!(pluralize("SalesPerson"), "SalesPeople");
+        assert_eq!(pluralize("HTTPRequest"), "HTTPRequests");
+        assert_eq!(pluralize("MyHTTPBox"), "MyHTTPBoxes");
+    }
+}
@@ -7,7 +7,7 @@ use boilermaker_web::{WebApp, WebAppState};
 #[tracing::instrument]
 async fn main() {
     color_eyre::install().expect("Failed to set up error handling");
-    let _ = logging::init_tracing(3);
+    let _ = logging::init_tracing(3, false);
 
     let app_state = Arc::new(
         WebAppState::new()